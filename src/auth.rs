@@ -0,0 +1,398 @@
+use crate::config::auth::{Config, Scope};
+use crate::error::{ApiError, ErrorCode};
+use axum::extract::{MatchedPath, Query, Request, State};
+use axum::http::{HeaderValue, Method, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use utoipa::openapi::OpenApi;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header of every token, which are encoded as a JWT signed with HS256
+const TOKEN_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+/// Claims of a signed bearer token
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Claims {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    pub scopes: Vec<Scope>,
+    /// Expiration of the token in seconds since the unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct Auth {
+    inner: Arc<Inner>,
+    /// Scope required by every route, keyed by the method and path of the route
+    routes: Arc<HashMap<(Method, String), Scope>>,
+}
+
+struct Inner {
+    config: Config,
+    secret: Option<Vec<u8>>,
+}
+
+impl Auth {
+    pub fn new(config: Config) -> std::io::Result<Self> {
+        let secret = config
+            .token_secret
+            .as_deref()
+            .map(decode_secret)
+            .transpose()?;
+        if !config.is_enabled() {
+            println!(
+                "WARNING: authentication is disabled since no api keys or token secret are configured. Anyone able to reach the api has full access to the node"
+            );
+        }
+
+        Ok(Self {
+            inner: Arc::new(Inner { config, secret }),
+            routes: Arc::default(),
+        })
+    }
+
+    /// Take the scope required by every route from the bearer security requirement of the route
+    /// in the specification. Every route is required to declare a scope, so a route could not be
+    /// left accessible by mistake
+    pub fn with_routes(mut self, openapi: &OpenApi) -> std::io::Result<Self> {
+        let mut routes = HashMap::new();
+
+        for (path, item) in openapi.paths.paths.iter() {
+            let operations = [
+                (Method::GET, &item.get),
+                (Method::PUT, &item.put),
+                (Method::POST, &item.post),
+                (Method::DELETE, &item.delete),
+                (Method::PATCH, &item.patch),
+            ];
+
+            for (method, operation) in operations {
+                let Some(operation) = operation else {
+                    continue;
+                };
+
+                let scope = operation
+                    .security
+                    .iter()
+                    .flatten()
+                    .filter_map(|requirement| serde_json::to_value(requirement).ok())
+                    .find_map(|requirement| {
+                        requirement
+                            .get("bearer")?
+                            .as_array()?
+                            .first()?
+                            .as_str()?
+                            .parse::<Scope>()
+                            .ok()
+                    })
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("route {method} {path} does not declare a scope"),
+                        )
+                    })?;
+
+                routes.insert((method, path.clone()), scope);
+            }
+        }
+
+        self.routes = Arc::new(routes);
+        Ok(self)
+    }
+
+    /// Returns the scopes granted by the credential, which is either an api key or a signed token
+    fn scopes(&self, credential: &str) -> Option<Vec<Scope>> {
+        if let Some(api_key) = self
+            .inner
+            .config
+            .api_keys
+            .iter()
+            .find(|api_key| constant_time_eq(api_key.key.as_bytes(), credential.as_bytes()))
+        {
+            return Some(api_key.scopes.clone());
+        }
+
+        let secret = self.inner.secret.as_deref()?;
+        let claims = verify_token(secret, credential)?;
+        Some(claims.scopes)
+    }
+}
+
+/// Middleware rejecting any request that does not provide a credential granting the scope
+/// declared by the route being accessed
+pub async fn authorize(State(auth): State<Auth>, request: Request, next: Next) -> Response {
+    if !auth.inner.config.is_enabled() {
+        return next.run(request).await;
+    }
+
+    // Requests that did not match any route are left to the fallback
+    let Some(path) = request.extensions().get::<MatchedPath>() else {
+        return next.run(request).await;
+    };
+
+    let method = match request.method() {
+        &Method::HEAD => Method::GET,
+        method => method.clone(),
+    };
+
+    let Some(required) = auth
+        .routes
+        .get(&(method, path.as_str().to_string()))
+        .cloned()
+    else {
+        return ApiError::new(ErrorCode::Forbidden, "route does not declare a scope")
+            .into_response();
+    };
+
+    let Some(scopes) = credential(&request).and_then(|credential| auth.scopes(&credential)) else {
+        let mut response =
+            ApiError::new(ErrorCode::Unauthorized, "missing or invalid credential").into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+
+    let group = required.group.as_deref().unwrap_or_default();
+
+    if !scopes
+        .iter()
+        .any(|scope| scope.allows(group, required.access))
+    {
+        return ApiError::new(
+            ErrorCode::Forbidden,
+            format!("credential does not grant {required}"),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct CredentialParam {
+    access_token: Option<String>,
+}
+
+/// Credential of the request, taken from the authorization header. Browsers cannot set headers
+/// on websockets and event streams, so those requests can provide it as `?access_token=` instead
+fn credential(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(bearer) = bearer {
+        return Some(bearer.trim().to_string());
+    }
+
+    let contains = |name, needle: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.to_ascii_lowercase().contains(needle))
+    };
+    if !contains(header::UPGRADE, "websocket") && !contains(header::ACCEPT, "text/event-stream") {
+        return None;
+    }
+
+    Query::<CredentialParam>::try_from_uri(request.uri())
+        .ok()?
+        .0
+        .access_token
+}
+
+/// Decodes the base64 encoded token secret
+pub fn decode_secret(secret: &str) -> std::io::Result<Vec<u8>> {
+    let secret = STANDARD
+        .decode(secret.trim())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    if secret.len() < 32 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "token secret must be at least 32 bytes",
+        ));
+    }
+
+    Ok(secret)
+}
+
+/// Sign the claims, producing a JWT using HS256
+pub fn sign_token(secret: &[u8], claims: &Claims) -> String {
+    let header = URL_SAFE_NO_PAD.encode(TOKEN_HEADER);
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).expect("valid claims"));
+    let message = format!("{header}.{payload}");
+
+    let mut mac = HmacSha256::new_from_slice(secret).expect("hmac accepts keys of any size");
+    mac.update(message.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    format!("{message}.{signature}")
+}
+
+/// Verifies the signature and expiration of the token, returning the claims if valid
+pub fn verify_token(secret: &[u8], token: &str) -> Option<Claims> {
+    let (message, signature) = token.rsplit_once('.')?;
+    let (header, payload) = message.split_once('.')?;
+
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    let mut mac = HmacSha256::new_from_slice(secret).ok()?;
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).ok()?;
+
+    let header: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+    if header.get("alg")?.as_str()? != "HS256" {
+        return None;
+    }
+
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+    if let Some(exp) = claims.exp {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if now >= exp {
+            return None;
+        }
+    }
+
+    Some(claims)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::auth::ApiKey;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use utoipa_axum::router::OpenApiRouter;
+    use utoipa_axum::routes;
+
+    #[utoipa::path(
+        post,
+        path = "/find",
+        tag = "test",
+        security(("bearer" = ["test:read"])),
+        responses((status = 200))
+    )]
+    async fn find() {}
+
+    #[utoipa::path(
+        post,
+        path = "/store",
+        tag = "test",
+        security(("bearer" = ["test:write"])),
+        responses((status = 200))
+    )]
+    async fn store() {}
+
+    #[utoipa::path(
+        get,
+        path = "/events",
+        tag = "test",
+        security(("bearer" = ["test:read"])),
+        responses((status = 200))
+    )]
+    async fn events() {}
+
+    #[utoipa::path(post, path = "/untagged", tag = "test", responses((status = 200)))]
+    async fn untagged() {}
+
+    fn auth(scopes: &str) -> Auth {
+        Auth::new(Config {
+            api_keys: vec![ApiKey {
+                key: "key".into(),
+                scopes: vec![scopes.parse().unwrap()],
+            }],
+            token_secret: None,
+        })
+        .unwrap()
+    }
+
+    async fn status(addr: std::net::SocketAddr, path: &str) -> String {
+        send(
+            addr,
+            &format!("POST {path} HTTP/1.1\r\nAuthorization: Bearer key\r\nContent-Length: 0\r\n"),
+        )
+        .await
+    }
+
+    /// Send the request line and headers provided, returning the status of the response
+    async fn send(addr: std::net::SocketAddr, head: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("{head}Host: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.split(' ').nth(1).unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn route_scopes() {
+        let (app, openapi) = OpenApiRouter::new()
+            .nest(
+                "/test",
+                OpenApiRouter::new()
+                    .routes(routes!(find))
+                    .routes(routes!(store)),
+            )
+            .split_for_parts();
+
+        let auth = auth("test:read").with_routes(&openapi).unwrap();
+        let app = app.layer(axum::middleware::from_fn_with_state(auth, authorize));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // The scope declared by the route decides the access, rather than the method used
+        assert_eq!(status(addr, "/test/find").await, "200");
+        assert_eq!(status(addr, "/test/store").await, "403");
+    }
+
+    #[tokio::test]
+    async fn query_credential_for_event_streams() {
+        let (app, openapi) = OpenApiRouter::new()
+            .nest("/test", OpenApiRouter::new().routes(routes!(events)))
+            .split_for_parts();
+
+        let auth = auth("test:read").with_routes(&openapi).unwrap();
+        let app = app.layer(axum::middleware::from_fn_with_state(auth, authorize));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let stream = "Accept: text/event-stream\r\n";
+        let get =
+            |query: &str, headers: &str| format!("GET /test/events{query} HTTP/1.1\r\n{headers}");
+        assert_eq!(send(addr, &get("?access_token=key", stream)).await, "200");
+        assert_eq!(send(addr, &get("?access_token=other", stream)).await, "401");
+        // Other requests can set the authorization header, so the query is not accepted
+        assert_eq!(send(addr, &get("?access_token=key", "")).await, "401");
+    }
+
+    #[test]
+    fn untagged_route_rejected() {
+        let (_, openapi) = OpenApiRouter::<()>::new()
+            .routes(routes!(untagged))
+            .split_for_parts();
+
+        assert!(auth("test").with_routes(&openapi).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Authentication for the api. Note that authentication is disabled if no api keys or token
+/// secret is provided
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub api_keys: Vec<ApiKey>,
+    /// Secret, encoded in base64, used to verify HMAC-SHA256 signed bearer tokens
+    pub token_secret: Option<String>,
+}
+
+impl Config {
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.token_secret.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    pub key: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Scope granting access to a group of routes, written as `<group>` for full access or
+/// `<group>:read`/`<group>:write` for read-only or write-only access.
+/// `*` can be used in place of the group to match every group
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Scope {
+    pub group: Option<String>,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    All,
+}
+
+impl Scope {
+    /// Returns true if the scope grants the access to the group
+    pub fn allows(&self, group: &str, access: Access) -> bool {
+        let group_matches = self.group.as_deref().is_none_or(|g| g == group);
+        let access_matches = self.access == Access::All || self.access == access;
+        group_matches && access_matches
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, access) = match s.split_once(':') {
+            Some((group, "read")) => (group, Access::Read),
+            Some((group, "write")) => (group, Access::Write),
+            Some((_, access)) => return Err(format!("invalid access \"{access}\" in scope {s}")),
+            None => (s, Access::All),
+        };
+
+        let group = match group {
+            "" => return Err(format!("missing group in scope {s}")),
+            "*" => None,
+            group => Some(group.to_string()),
+        };
+
+        Ok(Scope { group, access })
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let group = self.group.as_deref().unwrap_or("*");
+        match self.access {
+            Access::Read => write!(f, "{group}:read"),
+            Access::Write => write!(f, "{group}:write"),
+            Access::All => write!(f, "{group}"),
+        }
+    }
+}
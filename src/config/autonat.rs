@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Options for autonat, limiting the dial-back probes the node would perform for other peers
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Maximum number of probes served for all peers within `throttle_clients_period`
+    pub throttle_clients_global_max: usize,
+    /// Maximum number of probes served for a single peer within `throttle_clients_period`
+    pub throttle_clients_peer_max: usize,
+    /// Period, in seconds, in which the probe limits apply
+    pub throttle_clients_period: u64,
+    /// Only dial back addresses that are globally reachable
+    pub only_global_ips: bool,
+    /// Number of consistent probe results required to be fully confident in the nat status
+    pub confidence_max: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            throttle_clients_global_max: 30,
+            throttle_clients_peer_max: 3,
+            throttle_clients_period: 1,
+            only_global_ips: true,
+            confidence_max: 3,
+        }
+    }
+}
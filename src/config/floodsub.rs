@@ -1 +1,21 @@
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Deliver messages published by the node to its own subscriptions on the topic, as if they
+    /// were received from the network
+    pub subscribe_local_messages: bool,
+    /// Add peers to the floodsub view once connected so messages would be exchanged with them.
+    /// Peers could otherwise be added through `POST /floodsub/peers/add`
+    pub add_connected_peers: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            subscribe_local_messages: false,
+            add_connected_peers: true,
+        }
+    }
+}
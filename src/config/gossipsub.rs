@@ -1 +1,213 @@
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Hold inbound messages until they are validated by a http client. Messages on topics
+    /// without a validator would be accepted automatically
+    pub validate_messages: bool,
+    /// Target number of peers in the mesh of a topic (D)
+    pub mesh_n: usize,
+    /// Minimum number of peers in the mesh of a topic before more are added (D_low)
+    pub mesh_n_low: usize,
+    /// Maximum number of peers in the mesh of a topic before some are removed (D_high)
+    pub mesh_n_high: usize,
+    /// Interval between heartbeats, in milliseconds
+    pub heartbeat_interval: u64,
+    pub validation_mode: ValidationMode,
+    pub message_id: MessageId,
+    /// Maximum size of a message, in bytes
+    pub max_transmit_size: usize,
+    /// Publish messages to every peer subscribed to the topic instead of only the mesh
+    pub flood_publish: bool,
+    /// Parameters and thresholds used for peer scoring. Peer scoring is disabled if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peer_score: Option<PeerScore>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            validate_messages: false,
+            mesh_n: 6,
+            mesh_n_low: 5,
+            mesh_n_high: 12,
+            heartbeat_interval: 1000,
+            validation_mode: ValidationMode::default(),
+            message_id: MessageId::default(),
+            max_transmit_size: 65536,
+            flood_publish: true,
+            peer_score: None,
+        }
+    }
+}
+
+/// Validation of the author, sequence number and signature of inbound messages
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationMode {
+    /// Messages must be signed by their author
+    #[default]
+    Strict,
+    /// Messages are validated only if they have an author, sequence number or signature
+    Permissive,
+    /// Messages must not have an author, sequence number or signature. Messages published by
+    /// the node would be anonymous as well
+    Anonymous,
+    /// Messages are not validated
+    None,
+}
+
+/// Function used to derive the id of a message
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageId {
+    /// Source of the message along with its sequence number
+    #[default]
+    SourceSeqno,
+    /// Sha256 hash of the message data, so messages with the same data are treated as duplicates
+    Content,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct PeerScore {
+    pub params: PeerScoreParams,
+    pub thresholds: PeerScoreThresholds,
+    /// Score parameters of every topic the node subscribes to
+    pub topic: TopicScoreParams,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PeerScoreParams {
+    /// Maximum score contributed by topics. Zero for no cap
+    pub topic_score_cap: f64,
+    /// Weight of the penalty for peers sharing an ip address. Must be negative, or zero to disable
+    pub ip_colocation_factor_weight: f64,
+    /// Number of peers that may share an ip address before being penalized
+    pub ip_colocation_factor_threshold: f64,
+    /// Weight of the penalty for misbehaving peers. Must be negative, or zero to disable
+    pub behaviour_penalty_weight: f64,
+    /// Number of misbehaviours tolerated before a peer is penalized
+    pub behaviour_penalty_threshold: f64,
+    /// Decay of the behaviour penalty. Must be between 0 and 1
+    pub behaviour_penalty_decay: f64,
+    /// Interval between score decays, in seconds
+    pub decay_interval: u64,
+    /// Value below which a decayed counter is reset to zero. Must be between 0 and 1
+    pub decay_to_zero: f64,
+    /// Time the score of a disconnected peer is kept, in seconds
+    pub retain_score: u64,
+}
+
+impl Default for PeerScoreParams {
+    fn default() -> Self {
+        Self {
+            topic_score_cap: 3600.0,
+            ip_colocation_factor_weight: -5.0,
+            ip_colocation_factor_threshold: 10.0,
+            behaviour_penalty_weight: -10.0,
+            behaviour_penalty_threshold: 0.0,
+            behaviour_penalty_decay: 0.2,
+            decay_interval: 1,
+            decay_to_zero: 0.1,
+            retain_score: 3600,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PeerScoreThresholds {
+    /// Score below which gossip is not propagated to a peer. Must be negative
+    pub gossip_threshold: f64,
+    /// Score below which messages are not flood published to a peer. Must be less than or equal
+    /// to `gossip_threshold`
+    pub publish_threshold: f64,
+    /// Score below which messages from a peer are ignored. Must be less than or equal to
+    /// `publish_threshold`
+    pub graylist_threshold: f64,
+    /// Score above which peer exchange from a peer is accepted. Must be positive
+    pub accept_px_threshold: f64,
+    /// Median score of the mesh below which opportunistic grafting is triggered
+    pub opportunistic_graft_threshold: f64,
+}
+
+impl Default for PeerScoreThresholds {
+    fn default() -> Self {
+        Self {
+            gossip_threshold: -10.0,
+            publish_threshold: -50.0,
+            graylist_threshold: -80.0,
+            accept_px_threshold: 10.0,
+            opportunistic_graft_threshold: 20.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TopicScoreParams {
+    /// Weight of the topic in the score of a peer
+    pub topic_weight: f64,
+    /// Weight of the time a peer has been in the mesh of the topic
+    pub time_in_mesh_weight: f64,
+    /// Time in the mesh counted as one unit, in milliseconds
+    pub time_in_mesh_quantum: u64,
+    /// Maximum units of time in the mesh counted
+    pub time_in_mesh_cap: f64,
+    /// Weight of the messages a peer delivered first
+    pub first_message_deliveries_weight: f64,
+    /// Decay of the messages delivered first. Must be between 0 and 1
+    pub first_message_deliveries_decay: f64,
+    /// Maximum messages delivered first counted
+    pub first_message_deliveries_cap: f64,
+    /// Weight of the deficit of messages delivered by a peer in the mesh. Must be negative, or
+    /// zero to disable. Disabled by default as peers in the mesh of quiet topics would otherwise
+    /// be penalized
+    pub mesh_message_deliveries_weight: f64,
+    /// Decay of the messages delivered in the mesh. Must be between 0 and 1
+    pub mesh_message_deliveries_decay: f64,
+    /// Maximum messages delivered in the mesh counted
+    pub mesh_message_deliveries_cap: f64,
+    /// Messages a peer in the mesh is expected to deliver before being penalized
+    pub mesh_message_deliveries_threshold: f64,
+    /// Time after the first delivery of a message in which deliveries by other peers in the mesh
+    /// are counted, in milliseconds
+    pub mesh_message_deliveries_window: u64,
+    /// Time a peer must be in the mesh before its deliveries are penalized, in seconds
+    pub mesh_message_deliveries_activation: u64,
+    /// Weight of the deficit of a peer pruned from the mesh. Must be negative, or zero to disable
+    pub mesh_failure_penalty_weight: f64,
+    /// Decay of the mesh failure penalty. Must be between 0 and 1
+    pub mesh_failure_penalty_decay: f64,
+    /// Weight of the messages from a peer that were rejected. Must be negative, or zero to disable
+    pub invalid_message_deliveries_weight: f64,
+    /// Decay of the rejected messages. Must be between 0 and 1
+    pub invalid_message_deliveries_decay: f64,
+}
+
+impl Default for TopicScoreParams {
+    fn default() -> Self {
+        Self {
+            topic_weight: 0.5,
+            time_in_mesh_weight: 1.0,
+            time_in_mesh_quantum: 1,
+            time_in_mesh_cap: 3600.0,
+            first_message_deliveries_weight: 1.0,
+            first_message_deliveries_decay: 0.5,
+            first_message_deliveries_cap: 2000.0,
+            mesh_message_deliveries_weight: 0.0,
+            mesh_message_deliveries_decay: 0.5,
+            mesh_message_deliveries_cap: 100.0,
+            mesh_message_deliveries_threshold: 20.0,
+            mesh_message_deliveries_window: 10,
+            mesh_message_deliveries_activation: 5,
+            mesh_failure_penalty_weight: 0.0,
+            mesh_failure_penalty_decay: 0.5,
+            invalid_message_deliveries_weight: -1.0,
+            invalid_message_deliveries_decay: 0.3,
+        }
+    }
+}
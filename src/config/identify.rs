@@ -1,18 +1,20 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Options for identify. Note that the identify protocol itself cannot be changed
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
-    pub protocol: Option<String>,
     pub protocol_version: String,
     pub agent_version: String,
+    /// Push the listen addresses of the node to connected peers when they change
     pub push_address_update: bool,
+    /// Only send the external addresses of the node instead of including the listen addresses
     pub hide_listen_addrs: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            protocol: Some("/id/0.1.0".to_string()),
             protocol_version: "ipfs/0.1.0".to_string(),
             agent_version: "connexa-http/0.1.0".to_string(),
             push_address_update: true,
@@ -1,6 +1,49 @@
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
 
-#[derive(Serialize, Deserialize)]
+/// Options for kademlia. Intervals and ttls are in seconds, with `null` disabling them
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub protocol: Option<String>,
+    pub mode: Mode,
+    /// Number of peers a record is replicated to
+    pub replication_factor: NonZeroUsize,
+    /// Timeout of a single query, in seconds
+    pub query_timeout: u64,
+    pub record_ttl: Option<u64>,
+    pub record_replication_interval: Option<u64>,
+    pub record_publication_interval: Option<u64>,
+    pub provider_record_ttl: Option<u64>,
+    pub provider_publication_interval: Option<u64>,
+    /// Only store records and provider records from other peers once they are confirmed with
+    /// `POST /kademlia/records/{id}`
+    pub record_filtering: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            protocol: Some("/ipfs/kad/1.0.0".to_string()),
+            mode: Mode::default(),
+            replication_factor: NonZeroUsize::new(20).expect("not zero"),
+            query_timeout: 60,
+            record_ttl: Some(48 * 60 * 60),
+            record_replication_interval: Some(60 * 60),
+            record_publication_interval: Some(22 * 60 * 60),
+            provider_record_ttl: Some(48 * 60 * 60),
+            provider_publication_interval: Some(12 * 60 * 60),
+            record_filtering: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Switch between client and server based on whether the node has a confirmed external address
+    #[default]
+    Auto,
+    Client,
+    Server,
 }
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Options for discovering peers on the local network
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Dial peers once they are discovered
+    pub auto_dial: bool,
+}
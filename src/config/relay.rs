@@ -1 +1,39 @@
+use connexa::prelude::PeerId;
+use serde::{Deserialize, Serialize};
 
+/// Limits of the relay server, for running a relay that is reachable by the public
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Maximum number of reservations accepted across all peers
+    pub max_reservations: usize,
+    /// Maximum number of reservations accepted from a single peer
+    pub max_reservations_per_peer: usize,
+    /// Duration, in seconds, of a reservation before it has to be renewed
+    pub reservation_duration: u64,
+    /// Maximum number of circuits relayed across all peers
+    pub max_circuits: usize,
+    /// Maximum number of circuits relayed for a single peer
+    pub max_circuits_per_peer: usize,
+    /// Duration, in seconds, after which a circuit is closed
+    pub max_circuit_duration: u64,
+    /// Number of bytes relayed in each direction after which a circuit is closed
+    pub max_circuit_bytes: u64,
+    /// Peers permitted to make a reservation. Every peer is permitted when unset
+    pub allowed_peers: Option<Vec<PeerId>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_reservations: 128,
+            max_reservations_per_peer: 4,
+            reservation_duration: 60 * 60,
+            max_circuits: 16,
+            max_circuits_per_peer: 4,
+            max_circuit_duration: 2 * 60,
+            max_circuit_bytes: 1 << 17,
+            allowed_peers: None,
+        }
+    }
+}
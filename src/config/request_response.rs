@@ -1,10 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
-    protocol: String,
-    max_request_size: usize,
-    max_response_size: usize,
+    pub protocol: String,
+    pub max_request_size: usize,
+    pub max_response_size: usize,
+    /// Number of seconds to wait for a response before the request times out
+    pub timeout: u64,
 }
 
 impl Default for Config {
@@ -13,6 +16,7 @@ impl Default for Config {
             protocol: "/connexa-http/request-response".to_string(),
             max_request_size: 128 * 1024,
             max_response_size: 1024 * 1024,
+            timeout: 10,
         }
     }
 }
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    /// Path to the PEM encoded certificate chain
+    pub certificates: PathBuf,
+    /// Path to the PEM encoded private key
+    pub private_key: PathBuf,
+    /// Path to the PEM encoded certificate authorities used to verify client certificates.
+    /// When provided, every client will be required to present a valid certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+    /// Seconds a client has to complete the handshake before the connection is closed
+    #[serde(default = "Config::default_handshake_timeout")]
+    pub handshake_timeout: u64,
+    /// Maximum number of handshakes performed at once. Connections are no longer accepted
+    /// until a handshake completes once reached
+    #[serde(default = "Config::default_max_handshakes")]
+    pub max_handshakes: usize,
+}
+
+impl Config {
+    fn default_handshake_timeout() -> u64 {
+        10
+    }
+
+    fn default_max_handshakes() -> usize {
+        128
+    }
+}
@@ -1,63 +1,49 @@
+mod auth;
 mod config;
+mod error;
+mod node;
+mod openapi;
 mod routes;
+mod server;
+mod state;
 
-use axum::Router;
-use clap::Parser;
-use connexa::prelude::{DefaultConnexaBuilder, Multiaddr, PeerId, Protocol};
-use std::net::{IpAddr, SocketAddr};
-use std::path::PathBuf;
-use tokio::net::TcpListener;
-
-const IPFS_BOOTSTRAP: &[(&str, &str)] = &[
-    (
-        "/ip4/104.131.131.82/tcp/4001",
-        "QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
-    ),
-    (
-        "/ip4/104.131.131.82/udp/4001/quic-v1",
-        "QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
-    ),
-    (
-        "/dnsaddr/bootstrap.libp2p.io",
-        "QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
-    ),
-    (
-        "/dnsaddr/bootstrap.libp2p.io",
-        "QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
-    ),
-    (
-        "/dnsaddr/bootstrap.libp2p.io",
-        "QmbLHAnMoJPWSCR5Zhtx6BHJX9KiKNN6tpvbUcqanj75Nb",
-    ),
-    (
-        "/dnsaddr/bootstrap.libp2p.io",
-        "QmcZf59bWwK5XFi76CZX8cbJ4BhTzzA3gU1ZjYZcYW3dwt",
-    ),
-    (
-        "/dnsaddr/va1.bootstrap.libp2p.io",
-        "12D3KooWKnDdG3iXw9eTFijk3EWSunZcFi54Zka4wmtqtt6rPxc8",
-    ),
-];
+use crate::auth::{Auth, Claims};
+use crate::config::auth::Scope;
+use crate::config::{Config, Http, Identity, KeyType};
+use crate::routes::request_response::RequestResponse;
+use crate::routes::stream::Streams;
+use crate::routes::swarm::ListenEvent;
+use crate::state::{AppState, NodeState};
+use clap::{Parser, Subcommand};
+use connexa::prelude::Multiaddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
 
 #[derive(Debug, Parser)]
 #[clap(name = "connexa-http")]
 struct Opt {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Path to a configuration file.
-    /// Note that if a configuration file is used, other options will be ignored
+    /// Note that any other options provided will override the values in the configuration file
     #[clap(long)]
     config: Option<PathBuf>,
 
-    /// Path to a keypair file with the keypair encoded in base64
+    /// Path to a keypair file with the keypair encoded in base64 or, for RSA, a PKCS#8 PEM.
+    /// Note that the file must only be accessible by the owner
     #[clap(long)]
     keypair_file: Option<PathBuf>,
 
-    /// Keypair encoded in base64. 
+    /// Keypair encoded in base64.
     /// Note that this option is not ideal to use in production as it would
     /// expose your keypair
     #[clap(long)]
     keypair: Option<String>,
 
-    /// Http port. Default is 8080
+    /// Http port. Default is 8080.
+    /// Note that this will override the port of every tcp address in the configuration file
     #[clap(long)]
     http_port: Option<u16>,
 
@@ -70,246 +56,263 @@ struct Opt {
     ipfs_bootstrap: bool,
 }
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let opt = Opt::parse();
-    // TODO: Construct connexa based on options provided from clap, prioritizing the config file over other cli options
-
-    let connexa = DefaultConnexaBuilder::new_identity()
-        .enable_quic()
-        .enable_tcp()
-        .enable_webrtc()
-        .enable_secure_websocket()
-        .with_request_response(vec![])
-        .with_gossipsub()
-        .with_kademlia()
-        .with_ping()
-        .with_peer_store()
-        .with_blacklist()
-        .with_autonat_v1()
-        .with_rendezvous_client()
-        .with_rendezvous_server()
-        .with_upnp()
-        .with_relay()
-        .with_relay_server()
-        .with_dcutr()
-        .build()?;
+impl Opt {
+    /// Load the configuration file, if any, and apply the options provided on top of it
+    fn into_config(self) -> std::io::Result<Config> {
+        let mut config = match self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
 
-    let peer_id = connexa.keypair().public().to_peer_id();
+        match (self.keypair, self.keypair_file) {
+            (Some(private_key), _) => {
+                let keypair = config::decode_keypair(&private_key)?;
+                config.identity = Identity {
+                    peer_id: keypair.public().to_peer_id(),
+                    private_key: Some(private_key),
+                    keypair_file: None,
+                };
+            }
+            (None, Some(path)) => {
+                let keypair = config::read_keypair_file(&path)?;
+                config.identity = Identity {
+                    peer_id: keypair.public().to_peer_id(),
+                    private_key: None,
+                    keypair_file: Some(path),
+                };
+            }
+            (None, None) => {}
+        }
 
-    connexa
-        .swarm()
-        .listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())
-        .await?;
-    connexa
-        .swarm()
-        .listen_on("/ip6/::/tcp/0".parse().unwrap())
-        .await?;
-    connexa
-        .swarm()
-        .listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse().unwrap())
-        .await?;
-    connexa
-        .swarm()
-        .listen_on("/ip6/::/udp/0/quic-v1".parse().unwrap())
-        .await?;
-    // We will exclude a listening address for websocket for the time being
-    connexa
-        .swarm()
-        .listen_on("/ip4/0.0.0.0/udp/0/webrtc-direct".parse().unwrap())
-        .await?;
-    connexa
-        .swarm()
-        .listen_on("/ip6/::/udp/0/webrtc-direct".parse().unwrap())
-        .await?;
-
-    tokio::task::yield_now().await;
-
-    let addrs = connexa.swarm().listening_addresses().await?;
-
-    for addr in addrs {
-        let addr = addr.with_p2p(peer_id).unwrap();
-        println!("Listening on: {}", addr);
+        if let Some(http_port) = self.http_port {
+            for http in config.http.iter_mut() {
+                if let Http::Tcp { port, .. } = http {
+                    *port = http_port;
+                }
+            }
+        }
+
+        config.bootstrap.extend(self.bootstrap);
+
+        Ok(config)
     }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Write a configuration file with a newly generated identity
+    Init {
+        /// Path of the configuration file
+        #[clap(default_value = "config.json")]
+        path: PathBuf,
+
+        /// Type of keypair to generate
+        #[clap(long, value_enum, default_value_t = KeyType::Ed25519)]
+        key_type: KeyType,
+
+        /// Store the keypair in a separate file instead of the configuration file
+        #[clap(long)]
+        keypair_file: Option<PathBuf>,
+
+        /// Overwrite any existing files
+        #[clap(long)]
+        force: bool,
+    },
+    /// Replace the identity in an existing configuration file with a newly generated one
+    Rotate {
+        /// Path of the configuration file
+        path: PathBuf,
+
+        /// Type of keypair to generate
+        #[clap(long, value_enum, default_value_t = KeyType::Ed25519)]
+        key_type: KeyType,
+    },
+    /// Issue a bearer token signed with the token secret from the configuration file
+    Token {
+        /// Path of the configuration file
+        path: PathBuf,
+
+        /// Scope granted by the token (eg. `swarm`, `kademlia:read` or `*:read`).
+        /// Can be provided multiple times
+        #[clap(long = "scope", required = true)]
+        scopes: Vec<Scope>,
+
+        /// Subject of the token
+        #[clap(long)]
+        subject: Option<String>,
+
+        /// Number of seconds until the token expires. If not provided, the token will not expire
+        #[clap(long)]
+        expires_in: Option<u64>,
+    },
+}
+
+impl Command {
+    fn run(self) -> std::io::Result<()> {
+        match self {
+            Command::Init {
+                path,
+                key_type,
+                keypair_file,
+                force,
+            } => {
+                let already_exists = |e: std::io::Error, path: &PathBuf| match e.kind() {
+                    std::io::ErrorKind::AlreadyExists => std::io::Error::new(
+                        e.kind(),
+                        format!(
+                            "{} already exists. Use --force to overwrite",
+                            path.display()
+                        ),
+                    ),
+                    _ => e,
+                };
+
+                let mut config = Config {
+                    identity: Identity::generate(key_type)?,
+                    ..Default::default()
+                };
+
+                if let Some(keypair_file) = keypair_file {
+                    let private_key = config.identity.private_key.take().unwrap_or_default();
+                    config::write_private(&keypair_file, private_key.as_bytes(), force)
+                        .map_err(|e| already_exists(e, &keypair_file))?;
+                    config.identity.keypair_file = Some(keypair_file);
+                }
+
+                config
+                    .save(&path, force)
+                    .map_err(|e| already_exists(e, &path))?;
+                println!(
+                    "Wrote configuration to {} with peer id {}",
+                    path.display(),
+                    config.identity.peer_id
+                );
+            }
+            Command::Rotate { path, key_type } => {
+                let mut config = Config::load(&path)?;
+                let old_peer_id = config.identity.peer_id;
+                let mut identity = Identity::generate(key_type)?;
+
+                // Both files are written in full before either is replaced. The configuration
+                // is renamed into place before the keypair file, so the old keypair is only
+                // replaced once the new peer id has been saved
+                let mut keypair_rename = None;
+                if let Some(keypair_file) = config.identity.keypair_file.take() {
+                    let private_key = identity.private_key.take().unwrap_or_default();
+                    let temp = temp_path(&keypair_file);
+                    config::write_private(&temp, private_key.as_bytes(), true)?;
+                    keypair_rename = Some((temp, keypair_file.clone()));
+                    identity.keypair_file = Some(keypair_file);
+                }
+
+                config.identity = identity;
+                let temp = temp_path(&path);
+                config.save(&temp, true)?;
+                std::fs::rename(&temp, &path)?;
 
-    if opt.ipfs_bootstrap {
-        for (addr, peer_id) in IPFS_BOOTSTRAP {
-            let peer_id: PeerId = peer_id.parse().expect("valid peer id");
-            let addr: Multiaddr = addr.parse().expect("valid addr");
-            connexa.dht().add_address(peer_id, addr).await?;
+                if let Some((temp, keypair_file)) = keypair_rename {
+                    std::fs::rename(temp, keypair_file)?;
+                }
+                println!(
+                    "Rotated identity in {} from {old_peer_id} to {}",
+                    path.display(),
+                    config.identity.peer_id
+                );
+            }
+            Command::Token {
+                path,
+                scopes,
+                subject,
+                expires_in,
+            } => {
+                let config = Config::load(&path)?;
+                let Some(secret) = config.auth.token_secret.as_deref() else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "configuration does not have a token secret",
+                    ));
+                };
+                let secret = auth::decode_secret(secret)?;
+                let exp = expires_in.map(|secs| {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    (now + Duration::from_secs(secs)).as_secs()
+                });
+                let claims = Claims {
+                    sub: subject,
+                    scopes,
+                    exp,
+                };
+                println!("{}", auth::sign_token(&secret, &claims));
+            }
         }
+
+        Ok(())
     }
+}
 
-    for addr in opt.bootstrap {
-        let Protocol::P2p(peer_id) = addr.iter().last().unwrap() else {
-            println!("bootstrap node {addr} missing peer id. Skipping...");
-            continue;
-        };
+/// Path of the temporary file written next to `path` before being renamed into place
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
 
-        if let Err(e) = connexa
-            .peer_store()
-            .add_address(peer_id, addr.clone())
-            .await
-        {
-            println!("failed to add bootstrap node {addr} to peer store: {e}");
-        }
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let mut opt = Opt::parse();
 
-        if let Err(e) = connexa
-            .peer_store()
-            .add_address(peer_id, addr.clone())
-            .await
-        {
-            println!("failed to add bootstrap node {addr} to dht: {e}");
-        }
+    if let Some(command) = opt.command.take() {
+        return command.run();
     }
 
-    let gossipsub_routes = Router::new()
-        .route(
-            "/subscribe",
-            axum::routing::post(routes::gossipsub::subscribe),
-        )
-        .route(
-            "/topic/{name}",
-            axum::routing::get(routes::gossipsub::topic_listener),
-        )
-        .route(
-            "/topic/{name}/unsubscribe",
-            axum::routing::delete(routes::gossipsub::unsubscribe),
-        )
-        .route(
-            "/topic/{name}/peers",
-            axum::routing::get(routes::gossipsub::peers),
-        )
-        .route(
-            "/topic/{name}/publish",
-            axum::routing::put(routes::gossipsub::publish),
-        );
-
-    let floodsub_routes = Router::new()
-        .route(
-            "/subscribe",
-            axum::routing::post(routes::floodsub::subscribe),
-        )
-        .route(
-            "/topic/{name}",
-            axum::routing::get(routes::floodsub::topic_listener),
-        )
-        .route(
-            "/topic/{name}/unsubscribe",
-            axum::routing::delete(routes::floodsub::unsubscribe),
-        )
-        .route(
-            "/topic/{name}/publish",
-            axum::routing::put(routes::floodsub::publish),
-        );
-
-    let kad_routes = Router::new()
-        .route("/", axum::routing::get(routes::kademlia::listener))
-        .route(
-            "/find_peer",
-            axum::routing::post(routes::kademlia::find_peer),
-        )
-        .route("/provide", axum::routing::get(routes::kademlia::provide))
-        .route(
-            "/stop_provide",
-            axum::routing::delete(routes::kademlia::stop_provide),
-        )
-        .route(
-            "/get_providers",
-            axum::routing::get(routes::kademlia::get_providers),
-        )
-        .route(
-            "/bootstrap",
-            axum::routing::post(routes::kademlia::bootstrap),
-        )
-        .route("/get", axum::routing::get(routes::kademlia::get))
-        .route("/put", axum::routing::post(routes::kademlia::put))
-        .route(
-            "/add_address",
-            axum::routing::post(routes::kademlia::add_address),
-        );
-
-    let rz_routes = Router::new()
-        .route(
-            "/register",
-            axum::routing::post(routes::rendezvous::register),
-        )
-        .route(
-            "/unregister",
-            axum::routing::delete(routes::rendezvous::unregister),
-        )
-        .route(
-            "/discover",
-            axum::routing::post(routes::rendezvous::discovery),
-        );
-
-    let blacklist_route = Router::new()
-        .route("/add", axum::routing::post(routes::blacklist::add))
-        .route("/remove", axum::routing::delete(routes::blacklist::remove))
-        .route("/list", axum::routing::get(routes::blacklist::list));
-
-    let whitelist_route = Router::new()
-        .route("/add", axum::routing::post(routes::whitelist::add))
-        .route("/remove", axum::routing::delete(routes::whitelist::remove))
-        .route("/list", axum::routing::get(routes::whitelist::list));
-
-    let peerstore_route = Router::new()
-        .route("/add", axum::routing::post(routes::peerstore::add))
-        .route("/remove", axum::routing::delete(routes::peerstore::remove))
-        .route("/list", axum::routing::get(routes::peerstore::list));
-
-    let swarm_route = Router::new()
-        .route("/dial", axum::routing::post(routes::swarm::dial))
-        .route(
-            "/disconnect",
-            axum::routing::delete(routes::swarm::disconnect),
-        )
-        .route(
-            "/is_connected",
-            axum::routing::get(routes::swarm::is_connected),
-        )
-        .route(
-            "/connected",
-            axum::routing::get(routes::swarm::connected_peers),
-        )
-        .route(
-            "/addresses",
-            axum::routing::get(routes::swarm::listening_addresses),
-        )
-        // .route("/listen_on", axum::routing::post(routes::swarm::listen_on))
-        .route(
-            "/external_addresses",
-            axum::routing::get(routes::swarm::external_addresses),
-        )
-        .route(
-            "/listening_addresses",
-            axum::routing::get(routes::swarm::listening_addresses),
-        )
-        .route(
-            "/add_peer_address",
-            axum::routing::post(routes::swarm::add_peer_address),
-        )
-        .route(
-            "/listener",
-            axum::routing::get(routes::swarm::connection_listener),
-        );
-
-    let app = Router::new()
-        .nest("/gossipsub", gossipsub_routes)
-        .nest("/floodsub", floodsub_routes)
-        .nest("/kademlia", kad_routes)
-        .nest("/rendezvous", rz_routes)
-        .nest("/blacklist", blacklist_route)
-        .nest("/whitelist", whitelist_route)
-        .nest("/peerstore", peerstore_route)
-        .nest("/swarm", swarm_route)
-        .with_state(connexa);
-
-    let addr = SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)), 8080);
-
-    let listener = TcpListener::bind(addr).await?;
-    println!("Serving API at http://{addr}");
-    axum::serve(listener, app).await?;
+    let ipfs_bootstrap = opt.ipfs_bootstrap;
+    let config = opt.into_config()?;
+
+    let auth = Auth::new(config.auth.clone())?;
+
+    let state = NodeState::new(&config);
+
+    let connexa = node::build(&config, &state)?;
+
+    let peer_id = connexa.keypair().public().to_peer_id();
+
+    // Listeners only report their addresses once the node handles their events
+    let mut listen_events = state.listeners.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match listen_events.recv().await {
+                Ok(ListenEvent::NewListenAddr { address, .. }) => {
+                    let addr = address.with_p2p(peer_id).unwrap();
+                    println!("Listening on: {}", addr);
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    node::start(&connexa, &config, &state).await?;
+
+    node::bootstrap(&connexa, &config, ipfs_bootstrap).await?;
+
+    let request_response = RequestResponse::new(config.request_response.clone());
+
+    let streams = Streams::new(node::stream_control(&connexa).await?);
+
+    let (app, openapi) = routes::router(request_response.max_size()).split_for_parts();
+
+    let auth = auth.with_routes(&openapi)?;
+
+    let app = app
+        .layer(axum::middleware::from_fn_with_state(auth, auth::authorize))
+        .with_state(AppState::new(connexa, request_response, streams, state));
+
+    // The specification is served without requiring authentication
+    let app = app.merge(openapi::routes(openapi, config.redoc));
+
+    server::serve(&config.http, app).await?;
 
     Ok(())
 }
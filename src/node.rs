@@ -0,0 +1,1084 @@
+use crate::config::Config;
+use crate::config::gossipsub::{MessageId as MessageIdFn, ValidationMode};
+use crate::config::kademlia::Mode as KademliaMode;
+use crate::routes::autonat::{Autonat, Reachability};
+use crate::routes::dcutr::Dcutr;
+use crate::routes::gossipsub::Validation;
+use crate::routes::identify::Identify;
+use crate::routes::mdns::Mdns;
+use crate::routes::ping::Ping;
+use crate::routes::relay::{Relay, RelayServer};
+use crate::routes::swarm::{Connections, Listeners};
+use crate::routes::upnp::{Gateway, Upnp};
+use crate::state::NodeState;
+use axum::body::Bytes;
+use connexa::behaviour::request_response::RequestResponseConfig;
+use connexa::behaviour::{Behaviour, BehaviourEvent};
+use connexa::builder::ConnexaBuilder;
+use connexa::dummy;
+use connexa::prelude::autonat;
+use connexa::prelude::dcutr;
+use connexa::prelude::dht::{Mode, StoreInserts};
+use connexa::prelude::gossipsub::{
+    self, IdentTopic, MessageAcceptance, MessageAuthenticity, MessageId, TopicHash,
+};
+use connexa::prelude::identify;
+use connexa::prelude::mdns;
+use connexa::prelude::peer_store::store::memory::MemoryStore;
+use connexa::prelude::ping;
+use connexa::prelude::relay;
+use connexa::prelude::relay::server::RateLimiter;
+use connexa::prelude::stream;
+use connexa::prelude::swarm::dial_opts::DialOpts;
+use connexa::prelude::swarm::{self, SwarmEvent};
+use connexa::prelude::transport::transport::ListenerId;
+use connexa::prelude::upnp;
+use connexa::prelude::{Multiaddr, PeerId, Protocol, StreamProtocol};
+use futures::channel::oneshot;
+use igd_next::SearchOptions;
+use libp2p_gossipsub::{PeerScoreParams, PeerScoreThresholds, TopicScoreParams};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+pub type Connexa = connexa::handle::Connexa<Command>;
+
+/// Commands handled by the node for functionality that connexa does not expose
+pub enum Command {
+    /// Publish a message, responding with the id of the message
+    GossipsubPublish {
+        topic: TopicHash,
+        data: Bytes,
+        resp: oneshot::Sender<std::io::Result<MessageId>>,
+    },
+    /// Enable peer scoring with the parameters and thresholds provided
+    GossipsubPeerScore {
+        params: PeerScoreParams,
+        thresholds: PeerScoreThresholds,
+        resp: oneshot::Sender<std::io::Result<()>>,
+    },
+    /// Apply the topic score parameters from the config to the topic, if peer scoring is enabled
+    GossipsubTopicScore {
+        topic: String,
+        resp: oneshot::Sender<std::io::Result<()>>,
+    },
+    /// Listen on the address, recording the listener before any of its events are handled
+    ListenOn {
+        address: Multiaddr,
+        /// Peer id of the relay when the address is a circuit address, recording the reservation
+        /// along with the listener
+        relay_peer_id: Option<PeerId>,
+        resp: oneshot::Sender<std::io::Result<ListenerId>>,
+    },
+    /// Remove the listener, responding with false if the listener does not exist
+    RemoveListener {
+        listener_id: ListenerId,
+        resp: oneshot::Sender<bool>,
+    },
+    /// Create a control for opening and accepting streams, responding with none if streams are
+    /// not enabled
+    StreamControl {
+        resp: oneshot::Sender<Option<stream::Control>>,
+    },
+}
+
+type Swarm = swarm::Swarm<Behaviour<dummy::Behaviour, MemoryStore>>;
+
+/// Swarm events handled by the node. Note that connexa only allows a single swarm event
+/// callback, so every event the node is interested in would be handled here
+struct SwarmEvents {
+    /// Keep track of the addresses of every listener
+    listeners: Listeners,
+    /// Forward connection events to http clients
+    connections: Connections,
+    /// Accept gossipsub messages on topics without a http client validating them
+    validation: Option<Validation>,
+    /// Add peers to the floodsub view once connected
+    floodsub_peers: bool,
+    /// Cache identify info received from peers
+    identify: Option<Identify>,
+    /// Keep the latest ping results of peers
+    ping: Option<Ping>,
+    /// Keep the nat status of the node
+    autonat: Option<Autonat>,
+    /// Keep track of reservations on relays
+    relay: Option<Relay>,
+    /// Keep track of reservations and circuits served as a relay
+    relay_server: Option<RelayServer>,
+    /// Report attempts to upgrade relayed connections
+    dcutr: Option<Dcutr>,
+    /// Keep track of ports mapped on the gateway
+    upnp: Option<Upnp>,
+    /// Keep track of peers discovered on the local network
+    mdns: Option<Mdns>,
+    /// Dial peers discovered on the local network
+    mdns_auto_dial: bool,
+}
+
+impl SwarmEvents {
+    fn new(listeners: Listeners, connections: Connections) -> Self {
+        Self {
+            listeners,
+            connections,
+            validation: None,
+            floodsub_peers: false,
+            identify: None,
+            ping: None,
+            autonat: None,
+            relay: None,
+            relay_server: None,
+            dcutr: None,
+            upnp: None,
+            mdns: None,
+            mdns_auto_dial: false,
+        }
+    }
+
+    fn handle(
+        &self,
+        swarm: &mut Swarm,
+        event: &SwarmEvent<BehaviourEvent<dummy::Behaviour, MemoryStore>>,
+    ) {
+        self.connections.notify(event);
+
+        match event {
+            SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source,
+                message_id,
+                message,
+            })) => {
+                let Some(validation) = &self.validation else {
+                    return;
+                };
+
+                // Messages are only held for http clients validating the topic
+                if validation.is_validating(&message.topic) {
+                    return;
+                }
+
+                if let Some(gossipsub) = swarm.behaviour_mut().gossipsub.as_mut() {
+                    gossipsub.report_message_validation_result(
+                        message_id,
+                        propagation_source,
+                        MessageAcceptance::Accept,
+                    );
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Identify(event)) => {
+                let Some(identify) = &self.identify else {
+                    return;
+                };
+
+                match event {
+                    identify::Event::Received { peer_id, info, .. } => {
+                        identify.received(*peer_id, info)
+                    }
+                    identify::Event::Pushed { peer_id, info, .. } => {
+                        identify.pushed(*peer_id, *swarm.local_peer_id(), info)
+                    }
+                    _ => {}
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event {
+                peer,
+                connection,
+                result,
+            })) => {
+                if let Some(ping) = &self.ping {
+                    ping.result(*peer, *connection, result);
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::AutonatV1(event)) => {
+                // autonat only reports a status once it flips to public or private, so the node
+                // is unknown until then. Probes could change the confidence without changing the
+                // status, so the rest is read from the behaviour after every event
+                if let (Some(autonat), Some(behaviour)) =
+                    (&self.autonat, swarm.behaviour().autonat_v1.as_ref())
+                {
+                    let reachability = match event {
+                        autonat::v1::Event::StatusChanged { new, .. } if new.is_public() => {
+                            Some(Reachability::Public)
+                        }
+                        autonat::v1::Event::StatusChanged { .. } => Some(Reachability::Private),
+                        _ => None,
+                    };
+                    autonat.update(
+                        reachability,
+                        behaviour.public_address().cloned(),
+                        behaviour.confidence(),
+                    );
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::RelayClient(event)) => {
+                let Some(relay) = &self.relay else {
+                    return;
+                };
+
+                match event {
+                    relay::client::Event::ReservationReqAccepted {
+                        relay_peer_id,
+                        renewal,
+                        ..
+                    } => relay.accepted(*relay_peer_id, *renewal),
+                    relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. } => {
+                        relay.outbound_circuit(*relay_peer_id)
+                    }
+                    relay::client::Event::InboundCircuitEstablished { src_peer_id, .. } => {
+                        relay.inbound_circuit(*src_peer_id)
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Relay(event)) => {
+                let Some(server) = &self.relay_server else {
+                    return;
+                };
+
+                match event {
+                    relay::server::Event::ReservationReqAccepted { src_peer_id, .. } => {
+                        server.reservation_accepted(*src_peer_id)
+                    }
+                    relay::server::Event::ReservationReqDenied { .. } => {
+                        server.reservation_denied()
+                    }
+                    relay::server::Event::ReservationClosed { src_peer_id }
+                    | relay::server::Event::ReservationTimedOut { src_peer_id } => {
+                        server.reservation_closed(src_peer_id)
+                    }
+                    relay::server::Event::CircuitReqAccepted {
+                        src_peer_id,
+                        dst_peer_id,
+                    } => server.circuit_accepted(*src_peer_id, *dst_peer_id),
+                    relay::server::Event::CircuitReqDenied { .. } => server.circuit_denied(),
+                    relay::server::Event::CircuitClosed {
+                        src_peer_id,
+                        dst_peer_id,
+                        ..
+                    } => server.circuit_closed(*src_peer_id, *dst_peer_id),
+                    _ => {}
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Dcutr(dcutr::Event {
+                remote_peer_id,
+                result,
+            })) => {
+                if let Some(dcutr) = &self.dcutr {
+                    dcutr.result(*remote_peer_id, result);
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Upnp(event)) => {
+                let Some(upnp) = &self.upnp else {
+                    return;
+                };
+
+                match event {
+                    upnp::Event::NewExternalAddr(address) => {
+                        upnp.new_external_addr(address.clone())
+                    }
+                    upnp::Event::ExpiredExternalAddr(address) => {
+                        upnp.expired_external_addr(address)
+                    }
+                    upnp::Event::GatewayNotFound => upnp.failed(Gateway::NotFound),
+                    upnp::Event::NonRoutableGateway => upnp.failed(Gateway::NonRoutable),
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Mdns(event)) => {
+                let Some(mdns) = &self.mdns else {
+                    return;
+                };
+
+                match event {
+                    mdns::Event::Discovered(list) => {
+                        for (peer_id, addresses) in group_by_peer(list) {
+                            if self.mdns_auto_dial && !swarm.is_connected(&peer_id) {
+                                let opts = DialOpts::peer_id(peer_id)
+                                    .addresses(addresses.clone())
+                                    .build();
+                                // Failures would be reported as connection events
+                                _ = swarm.dial(opts);
+                            }
+                            mdns.discovered(peer_id, addresses);
+                        }
+                    }
+                    mdns::Event::Expired(list) => {
+                        for (peer_id, addresses) in group_by_peer(list) {
+                            mdns.expired(peer_id, addresses);
+                        }
+                    }
+                }
+            }
+            SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } => self.listeners.new_listen_addr(*listener_id, address),
+            SwarmEvent::ExpiredListenAddr {
+                listener_id,
+                address,
+            } => {
+                if let Some(autonat) = &self.autonat {
+                    autonat.address_expired(address);
+                }
+                self.listeners.expired_listen_addr(*listener_id, address);
+            }
+            SwarmEvent::ExternalAddrExpired { address } => {
+                if let Some(autonat) = &self.autonat {
+                    autonat.address_expired(address);
+                }
+            }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                reason,
+                ..
+            } => {
+                let error = reason.as_ref().err().map(ToString::to_string);
+                if let Some(relay) = &self.relay {
+                    relay.closed(*listener_id, error.clone());
+                }
+                self.listeners.closed(*listener_id, error);
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
+                // Only the node accepting the relayed connection initiates hole punching
+                if let Some(dcutr) = &self.dcutr
+                    && endpoint.is_relayed()
+                    && endpoint.is_listener()
+                {
+                    dcutr.attempt(*peer_id, *connection_id);
+                }
+
+                if self.floodsub_peers
+                    && num_established.get() == 1
+                    && let Some(floodsub) = swarm.behaviour_mut().floodsub.as_mut()
+                {
+                    floodsub.add_node_to_partial_view(*peer_id);
+                }
+            }
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established: 0,
+                ..
+            } => {
+                if let Some(identify) = &self.identify {
+                    identify.remove(peer_id);
+                }
+
+                if let Some(ping) = &self.ping {
+                    ping.remove(peer_id);
+                }
+
+                if let Some(server) = &self.relay_server {
+                    server.disconnected(peer_id);
+                }
+
+                // Otherwise floodsub would keep redialing the peer
+                if self.floodsub_peers
+                    && let Some(floodsub) = swarm.behaviour_mut().floodsub.as_mut()
+                {
+                    floodsub.remove_node_from_partial_view(peer_id);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Group the addresses reported by mdns by peer
+fn group_by_peer(list: &[(PeerId, Multiaddr)]) -> HashMap<PeerId, Vec<Multiaddr>> {
+    let mut peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+    for (peer_id, address) in list {
+        peers.entry(*peer_id).or_default().push(address.clone());
+    }
+    peers
+}
+
+/// Only permits the listed peers to make a reservation on the relay server
+struct AllowList(HashSet<PeerId>);
+
+impl RateLimiter for AllowList {
+    fn try_next(&mut self, peer: PeerId, _: &Multiaddr, _: Instant) -> bool {
+        self.0.contains(&peer)
+    }
+}
+
+const IPFS_BOOTSTRAP: &[(&str, &str)] = &[
+    (
+        "/ip4/104.131.131.82/tcp/4001",
+        "QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
+    ),
+    (
+        "/ip4/104.131.131.82/udp/4001/quic-v1",
+        "QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ",
+    ),
+    (
+        "/dnsaddr/bootstrap.libp2p.io",
+        "QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+    ),
+    (
+        "/dnsaddr/bootstrap.libp2p.io",
+        "QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
+    ),
+    (
+        "/dnsaddr/bootstrap.libp2p.io",
+        "QmbLHAnMoJPWSCR5Zhtx6BHJX9KiKNN6tpvbUcqanj75Nb",
+    ),
+    (
+        "/dnsaddr/bootstrap.libp2p.io",
+        "QmcZf59bWwK5XFi76CZX8cbJ4BhTzzA3gU1ZjYZcYW3dwt",
+    ),
+    (
+        "/dnsaddr/va1.bootstrap.libp2p.io",
+        "12D3KooWKnDdG3iXw9eTFijk3EWSunZcFi54Zka4wmtqtt6rPxc8",
+    ),
+];
+
+/// Construct a connexa node based on the protocols and transports enabled in the config
+pub fn build(config: &Config, state: &NodeState) -> std::io::Result<Connexa> {
+    let keypair = config.identity.keypair()?;
+    let protocols = &config.protocols;
+    let transports = &config.transports;
+
+    let topic_score = match &config.gossipsub.peer_score {
+        Some(peer_score) if protocols.gossipsub => {
+            let params = topic_score_params(&peer_score.topic);
+            params
+                .validate()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            Some(params)
+        }
+        _ => None,
+    };
+
+    let listeners = state.listeners.clone();
+    let relay = state.relay.clone();
+
+    let mut builder =
+        ConnexaBuilder::<dummy::Behaviour, (), Command, MemoryStore>::with_existing_identity(
+            keypair,
+        )?
+        .with_peer_store()
+        .with_blacklist()
+        .set_custom_task_callback(move |swarm, _, command| match command {
+            Command::GossipsubPublish { topic, data, resp } => {
+                let result = match swarm.behaviour_mut().gossipsub.as_mut() {
+                    Some(gossipsub) => gossipsub
+                        .publish(topic, data)
+                        .map_err(std::io::Error::other),
+                    None => Err(std::io::Error::other("gossipsub is not enabled")),
+                };
+                _ = resp.send(result);
+            }
+            Command::GossipsubPeerScore {
+                params,
+                thresholds,
+                resp,
+            } => {
+                let result = match swarm.behaviour_mut().gossipsub.as_mut() {
+                    Some(gossipsub) => gossipsub
+                        .with_peer_score(params, thresholds)
+                        .map_err(std::io::Error::other),
+                    None => Err(std::io::Error::other("gossipsub is not enabled")),
+                };
+                _ = resp.send(result);
+            }
+            Command::GossipsubTopicScore { topic, resp } => {
+                let result = match (swarm.behaviour_mut().gossipsub.as_mut(), &topic_score) {
+                    (Some(gossipsub), Some(params)) => gossipsub
+                        .set_topic_params(IdentTopic::new(topic), params.clone())
+                        .map_err(std::io::Error::other),
+                    (Some(_), None) => Ok(()),
+                    (None, _) => Err(std::io::Error::other("gossipsub is not enabled")),
+                };
+                _ = resp.send(result);
+            }
+            Command::ListenOn {
+                address,
+                relay_peer_id,
+                resp,
+            } => {
+                let result = swarm
+                    .listen_on(address.clone())
+                    .map_err(std::io::Error::other);
+                if let Ok(listener_id) = result {
+                    listeners.insert(listener_id, address.clone());
+                    if let Some(relay_peer_id) = relay_peer_id {
+                        relay.insert(listener_id, relay_peer_id, address);
+                    }
+                }
+                _ = resp.send(result);
+            }
+            Command::RemoveListener { listener_id, resp } => {
+                _ = resp.send(swarm.remove_listener(listener_id));
+            }
+            Command::StreamControl { resp } => {
+                let control = swarm.behaviour().stream.as_ref().map(|s| s.new_control());
+                _ = resp.send(control);
+            }
+        });
+
+    let mut events = SwarmEvents::new(state.listeners.clone(), state.connections.clone());
+
+    if transports.tcp {
+        builder = builder.enable_tcp();
+    }
+
+    if transports.udp {
+        builder = builder.enable_quic();
+    }
+
+    if transports.websocket {
+        builder = match (&config.websocket.keypair, &config.websocket.certificates) {
+            (Some(keypair), Some(certs)) => {
+                builder.enable_secure_websocket_with_pem(keypair.clone(), certs.clone())
+            }
+            _ => builder.enable_secure_websocket(),
+        };
+    }
+
+    if transports.webrtc_direct {
+        builder = match &config.webrtc.pem {
+            Some(pem) => builder.enable_webrtc_with_pem(pem),
+            None => builder.enable_webrtc(),
+        };
+    }
+
+    if protocols.identify {
+        let options = config.identify.clone();
+        builder =
+            builder.with_identify_with_config(options.protocol_version.clone(), move |config| {
+                config
+                    .with_agent_version(options.agent_version)
+                    .with_push_listen_addr_updates(options.push_address_update)
+                    .with_hide_listen_addrs(options.hide_listen_addrs)
+            });
+        events.identify = Some(state.identify.clone());
+    }
+
+    if protocols.autonat {
+        let options = config.autonat.clone();
+        builder = builder.with_autonat_v1_with_config(move |mut config| {
+            config.throttle_clients_global_max = options.throttle_clients_global_max;
+            config.throttle_clients_peer_max = options.throttle_clients_peer_max;
+            config.throttle_clients_period = Duration::from_secs(options.throttle_clients_period);
+            config.only_global_ips = options.only_global_ips;
+            config.confidence_max = options.confidence_max;
+            config
+        });
+        events.autonat = Some(state.autonat.clone());
+    }
+
+    if protocols.ping {
+        builder = builder.with_ping();
+        events.ping = Some(state.ping.clone());
+    }
+
+    if protocols.kademlia {
+        let kademlia = config.kademlia.clone();
+        let protocol = kademlia
+            .protocol
+            .clone()
+            .unwrap_or_else(|| "/ipfs/kad/1.0.0".to_string());
+        // connexa expects the protocol to be valid, so it is validated beforehand
+        StreamProtocol::try_from_owned(protocol.clone()).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid kademlia protocol: {e}"),
+            )
+        })?;
+
+        builder = builder.with_kademlia_with_config(protocol, move |mut config| {
+            let secs = |secs: Option<u64>| secs.map(Duration::from_secs);
+            config
+                .set_replication_factor(kademlia.replication_factor)
+                .set_query_timeout(Duration::from_secs(kademlia.query_timeout))
+                .set_record_ttl(secs(kademlia.record_ttl))
+                .set_replication_interval(secs(kademlia.record_replication_interval))
+                .set_publication_interval(secs(kademlia.record_publication_interval))
+                .set_provider_record_ttl(secs(kademlia.provider_record_ttl))
+                .set_provider_publication_interval(secs(kademlia.provider_publication_interval))
+                .set_record_filtering(match kademlia.record_filtering {
+                    true => StoreInserts::FilterBoth,
+                    false => StoreInserts::Unfiltered,
+                });
+            config
+        });
+    }
+
+    if protocols.floodsub {
+        let subscribe_local_messages = config.floodsub.subscribe_local_messages;
+        builder = builder.with_floodsub_with_config(move |mut config| {
+            config.subscribe_local_messages = subscribe_local_messages;
+            config
+        });
+        events.floodsub_peers = config.floodsub.add_connected_peers;
+    }
+
+    if protocols.gossipsub {
+        let gossipsub = config.gossipsub.clone();
+        // Only the mesh parameters of specific topics are validated by the builder
+        if !(gossipsub.mesh_n_low <= gossipsub.mesh_n && gossipsub.mesh_n <= gossipsub.mesh_n_high)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "gossipsub mesh parameters must satisfy mesh_n_low <= mesh_n <= mesh_n_high",
+            ));
+        }
+
+        // connexa expects the configuration to be valid, so it is validated beforehand
+        gossipsub_config(&gossipsub, &mut gossipsub::ConfigBuilder::default())
+            .build()
+            .map_err(std::io::Error::other)?;
+
+        builder = builder.with_gossipsub_with_config(move |keypair, mut config| {
+            gossipsub_config(&gossipsub, &mut config);
+            let authenticity = match gossipsub.validation_mode {
+                ValidationMode::Anonymous => MessageAuthenticity::Anonymous,
+                _ => MessageAuthenticity::Signed(keypair.clone()),
+            };
+            (config, authenticity)
+        });
+
+        if config.gossipsub.validate_messages {
+            events.validation = Some(state.validation.clone());
+        }
+    }
+
+    if protocols.relay {
+        let options = config.relay.clone();
+        builder = builder
+            .with_relay()
+            .with_relay_server_with_config(move |mut config| {
+                config.max_reservations = options.max_reservations;
+                config.max_reservations_per_peer = options.max_reservations_per_peer;
+                config.reservation_duration = Duration::from_secs(options.reservation_duration);
+                config.max_circuits = options.max_circuits;
+                config.max_circuits_per_peer = options.max_circuits_per_peer;
+                config.max_circuit_duration = Duration::from_secs(options.max_circuit_duration);
+                config.max_circuit_bytes = options.max_circuit_bytes;
+                if let Some(peers) = options.allowed_peers {
+                    config
+                        .reservation_rate_limiters
+                        .push(Box::new(AllowList(peers.into_iter().collect())));
+                }
+                config
+            });
+        events.relay = Some(state.relay.clone());
+        events.relay_server = Some(state.relay_server.clone());
+    }
+
+    if protocols.dcutr {
+        builder = builder.with_dcutr();
+        events.dcutr = Some(state.dcutr.clone());
+    }
+
+    if protocols.mdns {
+        builder = builder.with_mdns();
+        events.mdns = Some(state.mdns.clone());
+        events.mdns_auto_dial = config.mdns.auto_dial;
+    }
+
+    if protocols.upnp {
+        builder = builder.with_upnp();
+        events.upnp = Some(state.upnp.clone());
+    }
+
+    if protocols.request_response {
+        let configs = config
+            .request_response
+            .iter()
+            .map(|config| {
+                RequestResponseConfig::new(&config.protocol)
+                    .set_max_request_size(config.max_request_size)
+                    .set_max_response_size(config.max_response_size)
+                    .set_timeout(Duration::from_secs(config.timeout))
+            })
+            .collect();
+        builder = builder.with_request_response(configs);
+    }
+
+    if protocols.rendezvous {
+        builder = builder.with_rendezvous_client().with_rendezvous_server();
+    }
+
+    if protocols.stream {
+        builder = builder.with_streams();
+    }
+
+    builder = builder.set_swarm_event_callback(move |swarm, event, _| {
+        events.handle(swarm, event);
+    });
+
+    builder.build()
+}
+
+/// Convert the topic score parameters from the config
+fn topic_score_params(config: &crate::config::gossipsub::TopicScoreParams) -> TopicScoreParams {
+    TopicScoreParams {
+        topic_weight: config.topic_weight,
+        time_in_mesh_weight: config.time_in_mesh_weight,
+        time_in_mesh_quantum: Duration::from_millis(config.time_in_mesh_quantum),
+        time_in_mesh_cap: config.time_in_mesh_cap,
+        first_message_deliveries_weight: config.first_message_deliveries_weight,
+        first_message_deliveries_decay: config.first_message_deliveries_decay,
+        first_message_deliveries_cap: config.first_message_deliveries_cap,
+        mesh_message_deliveries_weight: config.mesh_message_deliveries_weight,
+        mesh_message_deliveries_decay: config.mesh_message_deliveries_decay,
+        mesh_message_deliveries_cap: config.mesh_message_deliveries_cap,
+        mesh_message_deliveries_threshold: config.mesh_message_deliveries_threshold,
+        mesh_message_deliveries_window: Duration::from_millis(
+            config.mesh_message_deliveries_window,
+        ),
+        mesh_message_deliveries_activation: Duration::from_secs(
+            config.mesh_message_deliveries_activation,
+        ),
+        mesh_failure_penalty_weight: config.mesh_failure_penalty_weight,
+        mesh_failure_penalty_decay: config.mesh_failure_penalty_decay,
+        invalid_message_deliveries_weight: config.invalid_message_deliveries_weight,
+        invalid_message_deliveries_decay: config.invalid_message_deliveries_decay,
+    }
+}
+
+/// Apply the gossipsub options from the config to the builder
+fn gossipsub_config<'a>(
+    config: &crate::config::gossipsub::Config,
+    builder: &'a mut gossipsub::ConfigBuilder,
+) -> &'a mut gossipsub::ConfigBuilder {
+    builder
+        .mesh_n(config.mesh_n)
+        .mesh_n_low(config.mesh_n_low)
+        .mesh_n_high(config.mesh_n_high)
+        .heartbeat_interval(Duration::from_millis(config.heartbeat_interval))
+        .max_transmit_size(config.max_transmit_size)
+        .flood_publish(config.flood_publish)
+        .validation_mode(match config.validation_mode {
+            ValidationMode::Strict => gossipsub::ValidationMode::Strict,
+            ValidationMode::Permissive => gossipsub::ValidationMode::Permissive,
+            ValidationMode::Anonymous => gossipsub::ValidationMode::Anonymous,
+            ValidationMode::None => gossipsub::ValidationMode::None,
+        });
+
+    if config.message_id == MessageIdFn::Content {
+        builder.message_id_fn(|message| MessageId::new(&Sha256::digest(&message.data)));
+    }
+
+    if config.validate_messages {
+        builder.validate_messages();
+    }
+
+    builder
+}
+
+/// Publish a message on the topic, returning the id of the message
+pub async fn publish(
+    connexa: &Connexa,
+    topic: TopicHash,
+    data: Bytes,
+) -> std::io::Result<MessageId> {
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::GossipsubPublish {
+            topic,
+            data,
+            resp: tx,
+        })
+        .await?;
+    rx.await.map_err(std::io::Error::other)?
+}
+
+/// Subscribe to the topic, scoring the peers of the topic if peer scoring is enabled
+pub async fn subscribe(connexa: &Connexa, topic: String) -> std::io::Result<()> {
+    connexa.gossipsub().subscribe(topic.clone()).await?;
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::GossipsubTopicScore { topic, resp: tx })
+        .await?;
+    rx.await.map_err(std::io::Error::other)?
+}
+
+/// Listen on the address, returning the id of the listener. Addresses the listener resolves to
+/// are reported through swarm events
+pub async fn listen_on(connexa: &Connexa, address: Multiaddr) -> std::io::Result<ListenerId> {
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::ListenOn {
+            address,
+            relay_peer_id: None,
+            resp: tx,
+        })
+        .await?;
+    rx.await.map_err(std::io::Error::other)?
+}
+
+/// Request a reservation on the relay by listening on its circuit address, returning the id of
+/// the listener
+pub async fn reserve(
+    connexa: &Connexa,
+    address: Multiaddr,
+    relay_peer_id: PeerId,
+) -> std::io::Result<ListenerId> {
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::ListenOn {
+            address,
+            relay_peer_id: Some(relay_peer_id),
+            resp: tx,
+        })
+        .await?;
+    rx.await.map_err(std::io::Error::other)?
+}
+
+/// Remove the listener, returning false if the listener does not exist
+pub async fn remove_listener(connexa: &Connexa, listener_id: ListenerId) -> std::io::Result<bool> {
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::RemoveListener {
+            listener_id,
+            resp: tx,
+        })
+        .await?;
+    rx.await.map_err(std::io::Error::other)
+}
+
+/// Create a control for the stream protocols of the node, which would be none if streams are
+/// not enabled
+pub async fn stream_control(connexa: &Connexa) -> std::io::Result<Option<stream::Control>> {
+    let (tx, rx) = oneshot::channel();
+    connexa
+        .send_custom_event(Command::StreamControl { resp: tx })
+        .await?;
+    rx.await.map_err(std::io::Error::other)
+}
+
+/// Start listening on the addresses provided in the config and announce any external addresses
+pub async fn start(connexa: &Connexa, config: &Config, state: &NodeState) -> std::io::Result<()> {
+    for addr in config.listen_on.iter().cloned() {
+        listen_on(connexa, addr).await?;
+    }
+
+    if config.protocols.upnp {
+        let upnp = state.upnp.clone();
+        tokio::spawn(async move { upnp.discover(SearchOptions::default()).await });
+    }
+
+    for addr in config.announce.iter().cloned() {
+        connexa.swarm().add_external_address(addr).await?;
+    }
+
+    if config.protocols.kademlia {
+        let mode = match config.kademlia.mode {
+            KademliaMode::Auto => None,
+            KademliaMode::Client => Some(Mode::Client),
+            KademliaMode::Server => Some(Mode::Server),
+        };
+        connexa.dht().set_mode(mode).await?;
+    }
+
+    if config.protocols.gossipsub
+        && let Some(peer_score) = &config.gossipsub.peer_score
+    {
+        let (params, thresholds) = (&peer_score.params, &peer_score.thresholds);
+        let (tx, rx) = oneshot::channel();
+        connexa
+            .send_custom_event(Command::GossipsubPeerScore {
+                params: PeerScoreParams {
+                    topic_score_cap: params.topic_score_cap,
+                    ip_colocation_factor_weight: params.ip_colocation_factor_weight,
+                    ip_colocation_factor_threshold: params.ip_colocation_factor_threshold,
+                    behaviour_penalty_weight: params.behaviour_penalty_weight,
+                    behaviour_penalty_threshold: params.behaviour_penalty_threshold,
+                    behaviour_penalty_decay: params.behaviour_penalty_decay,
+                    decay_interval: Duration::from_secs(params.decay_interval),
+                    decay_to_zero: params.decay_to_zero,
+                    retain_score: Duration::from_secs(params.retain_score),
+                    ..Default::default()
+                },
+                thresholds: PeerScoreThresholds {
+                    gossip_threshold: thresholds.gossip_threshold,
+                    publish_threshold: thresholds.publish_threshold,
+                    graylist_threshold: thresholds.graylist_threshold,
+                    accept_px_threshold: thresholds.accept_px_threshold,
+                    opportunistic_graft_threshold: thresholds.opportunistic_graft_threshold,
+                },
+                resp: tx,
+            })
+            .await?;
+        rx.await.map_err(std::io::Error::other)??;
+    }
+
+    Ok(())
+}
+
+/// Add the bootstrap nodes to the peer store and dht, if enabled
+pub async fn bootstrap(
+    connexa: &Connexa,
+    config: &Config,
+    ipfs_bootstrap: bool,
+) -> std::io::Result<()> {
+    if ipfs_bootstrap && config.protocols.kademlia {
+        for (addr, peer_id) in IPFS_BOOTSTRAP {
+            let peer_id: PeerId = peer_id.parse().expect("valid peer id");
+            let addr: Multiaddr = addr.parse().expect("valid addr");
+            connexa.dht().add_address(peer_id, addr).await?;
+        }
+    }
+
+    for addr in config.bootstrap.iter() {
+        let Some(Protocol::P2p(peer_id)) = addr.iter().last() else {
+            println!("bootstrap node {addr} missing peer id. Skipping...");
+            continue;
+        };
+
+        if let Err(e) = connexa
+            .peer_store()
+            .add_address(peer_id, addr.clone())
+            .await
+        {
+            println!("failed to add bootstrap node {addr} to peer store: {e}");
+        }
+
+        if !config.protocols.kademlia {
+            continue;
+        }
+
+        if let Err(e) = connexa.dht().add_address(peer_id, addr.clone()).await {
+            println!("failed to add bootstrap node {addr} to dht: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::{ProtocolFlags, TransportsFlags};
+    use connexa::prelude::FloodsubEvent;
+    use futures::StreamExt;
+
+    /// Node listening on a loopback tcp address with only the protocols provided enabled,
+    /// returning the node along with its listening address
+    pub(crate) async fn spawn_node(protocols: ProtocolFlags) -> (Connexa, Multiaddr) {
+        let config = Config {
+            listen_on: vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()],
+            protocols,
+            transports: TransportsFlags {
+                tcp: true,
+                udp: false,
+                websocket: false,
+                webrtc_direct: false,
+            },
+            request_response: vec![],
+            ..Default::default()
+        };
+        let state = NodeState::new(&config);
+        let connexa = build(&config, &state).unwrap();
+        start(&connexa, &config, &state).await.unwrap();
+
+        loop {
+            if let Some(addr) = connexa.swarm().listening_addresses().await.unwrap().pop() {
+                let peer_id = connexa.keypair().public().to_peer_id();
+                break (connexa, addr.with(Protocol::P2p(peer_id)));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    pub(crate) fn no_protocols() -> ProtocolFlags {
+        ProtocolFlags {
+            identify: false,
+            autonat: false,
+            ping: false,
+            kademlia: false,
+            gossipsub: false,
+            floodsub: false,
+            relay: false,
+            dcutr: false,
+            mdns: false,
+            upnp: false,
+            request_response: false,
+            rendezvous: false,
+            stream: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn floodsub_message_between_connected_peers() {
+        let protocols = || ProtocolFlags {
+            floodsub: true,
+            ..no_protocols()
+        };
+        let (a, _) = spawn_node(protocols()).await;
+        let (b, b_addr) = spawn_node(protocols()).await;
+        let b_peer_id = b.keypair().public().to_peer_id();
+
+        a.floodsub().subscribe("topic").await.unwrap();
+        b.floodsub().subscribe("topic").await.unwrap();
+        let mut a_events = a.floodsub().listener("topic").await.unwrap();
+        let mut b_events = b.floodsub().listener("topic").await.unwrap();
+
+        a.swarm().dial(b_addr).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            // Connected peers are added to the view, so the subscription of b reaches a
+            loop {
+                match a_events.next().await.unwrap() {
+                    FloodsubEvent::Subscribed { peer_id } if peer_id == b_peer_id => break,
+                    _ => continue,
+                }
+            }
+
+            a.floodsub()
+                .publish("topic", b"hello".to_vec())
+                .await
+                .unwrap();
+
+            loop {
+                if let FloodsubEvent::Message { message } = b_events.next().await.unwrap() {
+                    assert_eq!(message.data.as_ref(), b"hello");
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("message should be received");
+    }
+
+    #[tokio::test]
+    async fn peer_score_from_config() {
+        let mut config = Config {
+            listen_on: vec![],
+            protocols: ProtocolFlags {
+                gossipsub: true,
+                ..no_protocols()
+            },
+            request_response: vec![],
+            ..Default::default()
+        };
+        config.gossipsub.peer_score = Some(Default::default());
+        let state = NodeState::new(&config);
+        let connexa = build(&config, &state).unwrap();
+        start(&connexa, &config, &state).await.unwrap();
+
+        // Scoring can only be enabled once, so enabling it again fails if the config was applied
+        let (tx, rx) = oneshot::channel();
+        connexa
+            .send_custom_event(Command::GossipsubPeerScore {
+                params: PeerScoreParams::default(),
+                thresholds: PeerScoreThresholds::default(),
+                resp: tx,
+            })
+            .await
+            .unwrap();
+        assert!(rx.await.unwrap().is_err());
+
+        // Topic params are only accepted once scoring is enabled
+        subscribe(&connexa, "topic".into()).await.unwrap();
+
+        let peer_score = config.gossipsub.peer_score.as_mut().unwrap();
+        peer_score.topic.invalid_message_deliveries_weight = 1.0;
+        assert!(build(&config, &state).is_err());
+    }
+}
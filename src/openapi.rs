@@ -0,0 +1,192 @@
+use crate::error::{ApiError, ErrorCode};
+use axum::Router;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, OpenApi as OpenApiSpec, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+use utoipa_redoc::{Redoc, Servable};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "connexa-http", description = "HTTP API for a libp2p node"),
+    components(schemas(ApiError, ErrorCode)),
+    modifiers(&BearerAuth),
+    security((), ("bearer" = []))
+)]
+pub struct ApiDoc;
+
+/// Security scheme used when authentication is enabled
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut OpenApiSpec) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Describes the [`ApiError`] returned by every route that fails and prefixes the operation id
+/// with the tag of the route, since handlers of different groups share the same name
+struct Operations;
+
+impl Modify for Operations {
+    fn modify(&self, openapi: &mut OpenApiSpec) {
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.options,
+                &mut item.head,
+                &mut item.patch,
+                &mut item.trace,
+            ];
+            for operation in operations.into_iter().flatten() {
+                if let (Some(id), Some([tag, ..])) =
+                    (&operation.operation_id, operation.tags.as_deref())
+                {
+                    operation.operation_id = Some(format!("{tag}_{id}"));
+                }
+
+                operation
+                    .responses
+                    .responses
+                    .entry("default".into())
+                    .or_insert_with(|| {
+                        ResponseBuilder::new()
+                            .description("Error")
+                            .content(
+                                "application/json",
+                                ContentBuilder::new()
+                                    .schema(Some(Ref::from_schema_name("ApiError")))
+                                    .build(),
+                            )
+                            .build()
+                            .into()
+                    });
+            }
+        }
+    }
+}
+
+/// Routes serving the specification at `/openapi.json` and, if enabled, redoc at `/redoc`
+pub fn routes(mut openapi: OpenApiSpec, redoc: bool) -> Router {
+    Operations.modify(&mut openapi);
+
+    let mut router = Router::new();
+
+    if redoc {
+        router = router.merge(Redoc::with_url("/redoc", openapi.clone()));
+    }
+
+    router.route(
+        "/openapi.json",
+        axum::routing::get(move || std::future::ready(axum::Json(openapi.clone()))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+
+    fn spec() -> Value {
+        let (_, openapi) = crate::routes::router(0).split_for_parts();
+        serde_json::to_value(&openapi).unwrap()
+    }
+
+    fn operations(spec: &Value) -> impl Iterator<Item = (&String, &Value)> {
+        spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| item.as_object().unwrap().values().map(move |op| (path, op)))
+    }
+
+    /// Name of every handler annotated with `#[utoipa::path]` under `src/routes`
+    fn annotated_handlers() -> BTreeMap<String, usize> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/routes");
+        let mut handlers = BTreeMap::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let source = source.split("#[cfg(test)]").next().unwrap();
+            for annotated in source.split("#[utoipa::path(").skip(1) {
+                let name = annotated
+                    .split("pub async fn ")
+                    .nth(1)
+                    .and_then(|rest| rest.split(['(', '<']).next())
+                    .unwrap();
+                *handlers.entry(name.to_string()).or_default() += 1;
+            }
+        }
+        handlers
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        // routes added with `.route` would be served without appearing in the specification
+        let router = include_str!("routes/mod.rs");
+        assert!(
+            !router.contains(".route("),
+            "routes must be added with `routes!`"
+        );
+
+        let spec = spec();
+        let mut documented = BTreeMap::new();
+        for (_, op) in operations(&spec) {
+            let id = op["operationId"].as_str().unwrap().to_string();
+            *documented.entry(id).or_insert(0) += 1;
+        }
+
+        assert_eq!(documented, annotated_handlers());
+    }
+
+    #[test]
+    fn references_resolve() {
+        fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        found.push(r);
+                    }
+                    map.values().for_each(|v| refs(v, found));
+                }
+                Value::Array(values) => values.iter().for_each(|v| refs(v, found)),
+                _ => {}
+            }
+        }
+
+        let spec = spec();
+        let mut found = vec![];
+        refs(&spec, &mut found);
+        assert!(!found.is_empty());
+
+        for r in found {
+            let name = r.strip_prefix("#/components/schemas/").unwrap();
+            assert!(
+                spec["components"]["schemas"].get(name).is_some(),
+                "{r} does not resolve"
+            );
+        }
+    }
+
+    #[test]
+    fn json_responses_are_typed() {
+        let spec = spec();
+        for (path, op) in operations(&spec) {
+            for response in op["responses"].as_object().unwrap().values() {
+                let Some(schema) = response["content"].get("application/json") else {
+                    continue;
+                };
+                let schema = &schema["schema"];
+                assert!(
+                    schema.get("$ref").is_some() || schema.get("allOf").is_some(),
+                    "{path} responds with an untyped body: {schema}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json};
+use axum::extract::State;
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::Multiaddr;
+use futures::Stream;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Nat status of the node as determined by autonat, along with listeners of status changes
+#[derive(Clone)]
+pub struct Autonat {
+    enabled: bool,
+    status: Arc<Mutex<AutonatStatus>>,
+    events: broadcast::Sender<AutonatStatus>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    /// The node is reachable from the public address
+    Public,
+    /// The node is behind a nat and is not reachable
+    Private,
+    /// Not enough probes were performed to determine if the node is reachable
+    Unknown,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct AutonatStatus {
+    pub status: Reachability,
+    /// Address confirmed to be reachable, if the node is public
+    #[schema(value_type = Option<String>)]
+    pub public_address: Option<Multiaddr>,
+    /// Number of consecutive probes confirming the status
+    pub confidence: usize,
+}
+
+impl Default for AutonatStatus {
+    fn default() -> Self {
+        Self {
+            status: Reachability::Unknown,
+            public_address: None,
+            confidence: 0,
+        }
+    }
+}
+
+impl Autonat {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            status: Arc::default(),
+            events,
+        }
+    }
+
+    /// Update the status of the node, keeping the current reachability if none is provided, and
+    /// notify listeners if the reachability or public address changed
+    pub fn update(
+        &self,
+        reachability: Option<Reachability>,
+        public_address: Option<Multiaddr>,
+        confidence: usize,
+    ) {
+        let mut current = self.status.lock().expect("not poisoned");
+        let status = AutonatStatus {
+            status: reachability.unwrap_or(current.status),
+            public_address,
+            confidence,
+        };
+        let changed =
+            current.status != status.status || current.public_address != status.public_address;
+        *current = status.clone();
+        drop(current);
+
+        if changed {
+            _ = self.events.send(status);
+        }
+    }
+
+    /// Reset the status once the public address expires, since autonat does so without reporting
+    /// the change
+    pub fn address_expired(&self, address: &Multiaddr) {
+        let mut current = self.status.lock().expect("not poisoned");
+        if current.public_address.as_ref() != Some(address) {
+            return;
+        }
+        *current = AutonatStatus::default();
+        drop(current);
+
+        _ = self.events.send(AutonatStatus::default());
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "autonat")
+    }
+
+    fn get(&self) -> AutonatStatus {
+        self.status.lock().expect("not poisoned").clone()
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = autonat::StatusResponse)]
+pub struct StatusResponse {
+    pub nat_status: Reachability,
+    /// Address confirmed to be reachable, if the node is public
+    #[schema(value_type = Option<String>)]
+    pub public_address: Option<Multiaddr>,
+    /// Number of consecutive probes confirming the status
+    pub confidence: usize,
+    #[schema(value_type = Vec<String>)]
+    pub external_addresses: Vec<Multiaddr>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "autonat",
+    security(("bearer" = ["autonat:read"])),
+    responses((status = 200, description = "Nat status of the node along with its confirmed external addresses", body = ApiResponse<StatusResponse>))
+)]
+pub async fn status(
+    State(connexa): State<Connexa>,
+    State(autonat): State<Autonat>,
+) -> Result<Json<ApiResponse<StatusResponse>>, ApiError> {
+    autonat.ensure_enabled()?;
+
+    let status = autonat.get();
+    let external_addresses = connexa.swarm().external_addresses().await?;
+
+    Ok(ApiResponse::ok(StatusResponse {
+        nat_status: status.status,
+        public_address: status.public_address,
+        confidence: status.confidence,
+        external_addresses,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "autonat",
+    security(("bearer" = ["autonat:read"])),
+    responses((status = 200, description = "Stream of nat status changes", content_type = "text/event-stream", body = AutonatStatus))
+)]
+pub async fn listener(
+    State(autonat): State<Autonat>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    autonat.ensure_enabled()?;
+
+    Ok(routes::event_stream(autonat.events.subscribe()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_public_address_resets_status() {
+        let autonat = Autonat::new(true);
+        let mut events = autonat.events.subscribe();
+        let address: Multiaddr = "/ip4/203.0.113.1/tcp/4001".parse().unwrap();
+
+        autonat.update(Some(Reachability::Public), Some(address.clone()), 3);
+        assert_eq!(events.try_recv().unwrap().status, Reachability::Public);
+
+        autonat.address_expired(&"/ip4/203.0.113.2/tcp/4001".parse().unwrap());
+        assert_eq!(autonat.get().status, Reachability::Public);
+
+        autonat.address_expired(&address);
+        assert_eq!(autonat.get(), AutonatStatus::default());
+        assert_eq!(events.try_recv().unwrap(), AutonatStatus::default());
+    }
+}
@@ -1,54 +1,65 @@
-use axum::Json;
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Empty, Json};
 use axum::extract::State;
-use axum::http::StatusCode;
-use connexa::handle::Connexa;
 use connexa::prelude::PeerId;
-use serde::Deserialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+#[schema(as = blacklist::Param)]
 pub struct Param {
+    #[schema(value_type = String)]
     peer_id: PeerId,
 }
 
-pub async fn add(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match connexa.blacklist().add(param.peer_id).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = blacklist::Peers)]
+pub struct Peers {
+    #[schema(value_type = Vec<String>)]
+    peers: Vec<PeerId>,
 }
 
-pub async fn remove(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match connexa.blacklist().remove(param.peer_id).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+#[utoipa::path(
+    post,
+    path = "/add",
+    tag = "blacklist",
+    security(("bearer" = ["blacklist:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Peer added to the blacklist", body = ApiResponse<Empty>))
+)]
+pub async fn add(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.blacklist().add(param.peer_id).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn list(State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.blacklist().list().await {
-        Ok(peers) => {
-            Json(serde_json::json!({
-                "status": 200,
-                "peers": peers,
-            }))
-        }
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
-        }
-    }
+#[utoipa::path(
+    delete,
+    path = "/remove",
+    tag = "blacklist",
+    security(("bearer" = ["blacklist:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Peer removed from the blacklist", body = ApiResponse<Empty>))
+)]
+pub async fn remove(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.blacklist().remove(param.peer_id).await?;
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tag = "blacklist",
+    security(("bearer" = ["blacklist:read"])),
+    responses((status = 200, description = "Peers in the blacklist", body = ApiResponse<Peers>))
+)]
+pub async fn list(State(connexa): State<Connexa>) -> Result<Json<ApiResponse<Peers>>, ApiError> {
+    let peers = connexa.blacklist().list().await?;
+    Ok(ApiResponse::ok(Peers { peers }))
 }
@@ -0,0 +1,209 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::node::Connexa;
+use crate::routes::identify::Identify;
+use crate::routes::swarm::{Connections, Role, Transport};
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json, Query};
+use axum::extract::{Path, State};
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::swarm::ConnectionId;
+use connexa::prelude::swarm::dial_opts::{DialOpts, PeerCondition};
+use connexa::prelude::{PeerId, Protocol};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+use utoipa::{IntoParams, ToSchema};
+
+/// Listeners of hole punching attempts made to upgrade relayed connections to direct ones
+#[derive(Clone)]
+pub struct Dcutr {
+    enabled: bool,
+    events: broadcast::Sender<DcutrEvent>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DcutrEvent {
+    /// A relayed connection was accepted by the node, which is attempting to upgrade it through
+    /// hole punching
+    Attempt {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        /// Id of the relayed connection
+        connection_id: String,
+    },
+    /// A direct connection was established with the peer
+    Success {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        /// Id of the direct connection
+        connection_id: String,
+    },
+    /// Hole punching failed, leaving the peer connected through the relay
+    Failure {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        error: String,
+    },
+}
+
+impl DcutrEvent {
+    fn peer_id(&self) -> PeerId {
+        match self {
+            DcutrEvent::Attempt { peer_id, .. }
+            | DcutrEvent::Success { peer_id, .. }
+            | DcutrEvent::Failure { peer_id, .. } => *peer_id,
+        }
+    }
+}
+
+impl Dcutr {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { enabled, events }
+    }
+
+    /// Notify listeners that the node started hole punching over the relayed connection
+    pub fn attempt(&self, peer_id: PeerId, connection_id: ConnectionId) {
+        _ = self.events.send(DcutrEvent::Attempt {
+            peer_id,
+            connection_id: connection_id.to_string(),
+        });
+    }
+
+    /// Record the result of hole punching with the peer
+    pub fn result<E: ToString>(&self, peer_id: PeerId, result: &Result<ConnectionId, E>) {
+        let event = match result {
+            Ok(connection_id) => DcutrEvent::Success {
+                peer_id,
+                connection_id: connection_id.to_string(),
+            },
+            Err(e) => DcutrEvent::Failure {
+                peer_id,
+                error: e.to_string(),
+            },
+        };
+
+        _ = self.events.send(event);
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "dcutr")
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = dcutr::UpgradeResponse)]
+pub struct UpgradeResponse {
+    /// Id of the connection dialed to upgrade the relayed connection
+    pub connection_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/upgrade/{peer_id}",
+    tag = "dcutr",
+    security(("bearer" = ["dcutr:write"])),
+    params(("peer_id" = String, Path, description = "Peer connected through a relay")),
+    responses((status = 200, description = "Dialing the peer to upgrade to a direct connection, returning the id of the connection", body = ApiResponse<UpgradeResponse>))
+)]
+pub async fn upgrade(
+    Path(peer_id): Path<String>,
+    State(connexa): State<Connexa>,
+    State(dcutr): State<Dcutr>,
+    State(connections): State<Connections>,
+    State(identify): State<Identify>,
+) -> Result<Json<ApiResponse<UpgradeResponse>>, ApiError> {
+    dcutr.ensure_enabled()?;
+
+    let peer_id: PeerId = peer_id
+        .parse()
+        .map_err(|_| ApiError::invalid_request("invalid peer id"))?;
+
+    let connections = connections.list(Some(peer_id));
+    if connections.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("{peer_id} is not connected"),
+        ));
+    }
+
+    if connections
+        .iter()
+        .any(|info| info.transport != Transport::Relayed)
+    {
+        return Err(ApiError::invalid_request(format!(
+            "{peer_id} is already directly connected"
+        )));
+    }
+
+    // Hole punching is initiated by the peer that accepted the relayed connection, so dialing
+    // the peer through the relay again would have it attempt an upgrade on the new connection.
+    // Otherwise, the addresses the peer listens on are dialed directly.
+    let mut addresses: Vec<_> = connections
+        .into_iter()
+        .filter(|info| matches!(info.direction, Role::Dialer))
+        .map(|info| info.address)
+        .collect();
+
+    if addresses.is_empty() {
+        addresses = identify
+            .get(&peer_id)
+            .map(|info| info.listen_addrs)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|address| !address.iter().any(|p| matches!(p, Protocol::P2pCircuit)))
+            .collect();
+    }
+
+    if addresses.is_empty() {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("no address of {peer_id} to upgrade the connection with"),
+        ));
+    }
+
+    let opt = DialOpts::peer_id(peer_id)
+        .condition(PeerCondition::Always)
+        .addresses(addresses)
+        .build();
+
+    let id = connexa.swarm().dial(opt).await?;
+
+    Ok(ApiResponse::ok(UpgradeResponse {
+        connection_id: id.to_string(),
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListenerParam {
+    /// Only receive events for this peer
+    #[param(value_type = Option<String>)]
+    peer_id: Option<PeerId>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "dcutr",
+    security(("bearer" = ["dcutr:read"])),
+    params(ListenerParam),
+    responses((status = 200, description = "Stream of hole punching attempts, successes and failures", content_type = "text/event-stream", body = DcutrEvent))
+)]
+pub async fn listener(
+    Query(param): Query<ListenerParam>,
+    State(dcutr): State<Dcutr>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    dcutr.ensure_enabled()?;
+
+    Ok(routes::filtered_event_stream(
+        dcutr.events.subscribe(),
+        move |event| {
+            param
+                .peer_id
+                .is_none_or(|peer_id| event.peer_id() == peer_id)
+        },
+    ))
+}
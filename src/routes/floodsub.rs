@@ -1,101 +1,191 @@
-use axum::Json;
-use axum::body::Bytes;
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::pubsub::{Encoding, EncodingParam, Payload, PublishParam};
+use crate::routes::{ApiResponse, Empty, Json, Query};
 use axum::extract::{Path, State};
 use axum::response::Sse;
 use axum::response::sse::Event;
-use connexa::handle::Connexa;
 use connexa::prelude::{FloodsubEvent, FloodsubMessage, PeerId};
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::convert::Infallible;
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+#[schema(as = floodsub::SubscribeParam)]
 pub struct SubscribeParam {
     topic: String,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[schema(as = floodsub::PubsubEvent)]
 pub enum PubsubEvent {
-    Subscribed { peer_id: PeerId },
-    Unsubscribed { peer_id: PeerId },
-    Message { message: PubsubMessage },
+    Subscribed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+    },
+    Unsubscribed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+    },
+    Message {
+        message: PubsubMessage,
+    },
 }
 
-impl From<FloodsubEvent> for PubsubEvent {
-    fn from(ev: FloodsubEvent) -> Self {
+impl PubsubEvent {
+    pub fn new(ev: FloodsubEvent, encoding: Encoding) -> Self {
         match ev {
             FloodsubEvent::Subscribed { peer_id } => Self::Subscribed { peer_id },
             FloodsubEvent::Unsubscribed { peer_id } => Self::Unsubscribed { peer_id },
             FloodsubEvent::Message { message } => Self::Message {
-                message: message.into(),
+                message: PubsubMessage::new(message, encoding),
             },
         }
     }
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[schema(as = floodsub::PubsubMessage)]
 pub struct PubsubMessage {
+    #[schema(value_type = String)]
     pub source: PeerId,
-    pub data: Bytes,
+    /// Data encoded with the encoding requested by the listener
+    pub data: String,
     pub sequence_number: Vec<u8>,
 }
 
-impl From<FloodsubMessage> for PubsubMessage {
-    fn from(message: FloodsubMessage) -> Self {
+impl PubsubMessage {
+    pub fn new(message: FloodsubMessage, encoding: Encoding) -> Self {
         Self {
             source: message.source,
-            data: message.data,
+            data: encoding.encode(&message.data),
             sequence_number: message.sequence_number,
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/subscribe",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:write"])),
+    request_body = SubscribeParam,
+    responses((status = 200, description = "Subscribed to the topic", body = ApiResponse<Empty>))
+)]
 pub async fn subscribe(
     State(connexa): State<Connexa>,
     Json(param): Json<SubscribeParam>,
-) -> Json<Value> {
-    match connexa.floodsub().subscribe(param.topic).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.floodsub().subscribe(param.topic).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn unsubscribe(Path(topic): Path<String>, State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.floodsub().unsubscribe(topic).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+#[utoipa::path(
+    delete,
+    path = "/topic/{name}/unsubscribe",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:write"])),
+    params(("name" = String, Path, description = "Name of the topic")),
+    responses((status = 200, description = "Unsubscribed from the topic", body = ApiResponse<Empty>))
+)]
+pub async fn unsubscribe(
+    Path(topic): Path<String>,
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.floodsub().unsubscribe(topic).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
+#[utoipa::path(
+    put,
+    path = "/topic/{name}/publish",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:write"])),
+    params(("name" = String, Path, description = "Name of the topic")),
+    request_body(content(
+        (Vec<u8> = "application/octet-stream"),
+        (String = "text/plain"),
+        (PublishParam = "application/json"),
+    )),
+    responses((status = 200, description = "Message published to the topic", body = ApiResponse<Empty>))
+)]
 pub async fn publish(
     Path(topic): Path<String>,
     State(connexa): State<Connexa>,
-    Json(bytes): Json<Bytes>,
-) -> Json<Value> {
-    match connexa.floodsub().publish(topic, bytes).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+    Payload(bytes): Payload,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.floodsub().publish(topic, bytes).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
+#[utoipa::path(
+    get,
+    path = "/topic/{name}",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:read"])),
+    params(("name" = String, Path, description = "Name of the topic"), EncodingParam),
+    responses((status = 200, description = "Stream of events for the topic", content_type = "text/event-stream", body = PubsubEvent))
+)]
 pub async fn topic_listener(
     Path(topic): Path<String>,
+    Query(param): Query<EncodingParam>,
     State(connexa): State<Connexa>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut st = connexa
-        .floodsub()
-        .listener(topic)
-        .await
-        .unwrap_or(futures::stream::empty().boxed());
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut st = connexa.floodsub().listener(topic).await?;
 
-    Sse::new(async_stream::try_stream! {
+    Ok(Sse::new(async_stream::try_stream! {
         while let Some(ev) = st.next().await {
-            let event = PubsubEvent::from(ev);
+            let event = PubsubEvent::new(ev, param.encoding);
             if let Ok(event) = Event::default().json_data(event) {
                 yield event;
             }
         }
-    })
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = floodsub::PeerParam)]
+pub struct PeerParam {
+    #[schema(value_type = String)]
+    peer_id: PeerId,
+}
+
+#[utoipa::path(
+    post,
+    path = "/peers/add",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:write"])),
+    request_body = PeerParam,
+    responses((status = 200, description = "Peer added to the floodsub view", body = ApiResponse<Empty>))
+)]
+pub async fn add_peer(
+    State(connexa): State<Connexa>,
+    Json(param): Json<PeerParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa
+        .floodsub()
+        .add_node_to_partial_view(param.peer_id)
+        .await?;
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/peers/remove",
+    tag = "floodsub",
+    security(("bearer" = ["floodsub:write"])),
+    request_body = PeerParam,
+    responses((status = 200, description = "Peer removed from the floodsub view", body = ApiResponse<Empty>))
+)]
+pub async fn remove_peer(
+    State(connexa): State<Connexa>,
+    Json(param): Json<PeerParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa
+        .floodsub()
+        .remove_node_from_partial_view(param.peer_id)
+        .await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
@@ -1,113 +1,372 @@
-use axum::Json;
-use axum::body::Bytes;
+use crate::error::{ApiError, ErrorCode};
+use crate::node::{self, Connexa};
+use crate::routes::pubsub::{Encoding, EncodingParam, Payload, PublishParam};
+use crate::routes::{ApiResponse, Empty, Json, Query};
 use axum::extract::{Path, State};
 use axum::response::Sse;
 use axum::response::sse::Event;
-use connexa::handle::Connexa;
-use connexa::prelude::gossipsub::MessageId;
+use connexa::prelude::gossipsub::{IdentTopic, MessageAcceptance, MessageId, TopicHash};
 use connexa::prelude::{GossipsubEvent, GossipsubMessage, PeerId};
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+/// Duration a message would be held for validation before it is forgotten
+const VALIDATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Topics being validated by http clients along with the messages awaiting validation
+#[derive(Clone)]
+pub struct Validation {
+    enabled: bool,
+    inner: Arc<Mutex<ValidationState>>,
+}
+
+#[derive(Default)]
+struct ValidationState {
+    validators: HashMap<TopicHash, usize>,
+    pending: HashMap<String, PendingMessage>,
+}
+
+struct PendingMessage {
+    propagation_source: PeerId,
+    message_id: MessageId,
+    expires: Instant,
+}
+
+impl Validation {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            inner: Arc::default(),
+        }
+    }
+
+    /// Returns true if a http client is validating messages on the topic
+    pub fn is_validating(&self, topic: &TopicHash) -> bool {
+        let state = self.inner.lock().expect("not poisoned");
+        state.validators.contains_key(topic)
+    }
+
+    fn register(&self, topic: TopicHash) -> ValidatorGuard {
+        let mut state = self.inner.lock().expect("not poisoned");
+        *state.validators.entry(topic.clone()).or_default() += 1;
+        ValidatorGuard {
+            validation: self.clone(),
+            topic,
+        }
+    }
+
+    fn hold(&self, message_id: MessageId, propagation_source: PeerId) {
+        let now = Instant::now();
+        let mut state = self.inner.lock().expect("not poisoned");
+        state.pending.retain(|_, message| message.expires > now);
+        state.pending.insert(
+            message_id.to_string(),
+            PendingMessage {
+                propagation_source,
+                message_id,
+                expires: now + VALIDATION_TIMEOUT,
+            },
+        );
+    }
+
+    fn take(&self, id: &str) -> Option<(PeerId, MessageId)> {
+        let mut state = self.inner.lock().expect("not poisoned");
+        state
+            .pending
+            .remove(id)
+            .filter(|message| message.expires > Instant::now())
+            .map(|message| (message.propagation_source, message.message_id))
+    }
+}
+
+/// Unregisters the validator once the stream is dropped
+struct ValidatorGuard {
+    validation: Validation,
+    topic: TopicHash,
+}
+
+impl Drop for ValidatorGuard {
+    fn drop(&mut self) {
+        let mut state = self.validation.inner.lock().expect("not poisoned");
+        if let Some(count) = state.validators.get_mut(&self.topic) {
+            *count -= 1;
+            if *count == 0 {
+                state.validators.remove(&self.topic);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(as = gossipsub::SubscribeParam)]
 pub struct SubscribeParam {
     topic: String,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
+#[schema(as = gossipsub::PubsubEvent)]
 pub enum PubsubEvent {
-    Subscribed { peer_id: PeerId },
-    Unsubscribed { peer_id: PeerId },
-    Message { message: PubsubMessage },
+    Subscribed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+    },
+    Unsubscribed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+    },
+    Message {
+        message: PubsubMessage,
+    },
 }
 
-impl From<GossipsubEvent> for PubsubEvent {
-    fn from(ev: GossipsubEvent) -> Self {
+impl PubsubEvent {
+    pub fn new(ev: GossipsubEvent, encoding: Encoding) -> Self {
         match ev {
             GossipsubEvent::Subscribed { peer_id } => Self::Subscribed { peer_id },
             GossipsubEvent::Unsubscribed { peer_id } => Self::Unsubscribed { peer_id },
             GossipsubEvent::Message { message } => Self::Message {
-                message: message.into(),
+                message: PubsubMessage::new(message, encoding),
             },
         }
     }
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[schema(as = gossipsub::PubsubMessage)]
 pub struct PubsubMessage {
-    pub message_id: MessageId,
+    /// Id of the message encoded in hex
+    pub message_id: String,
+    #[schema(value_type = String)]
     pub propagated_source: PeerId,
+    #[schema(value_type = Option<String>)]
     pub source: Option<PeerId>,
-    pub data: Bytes,
+    /// Data encoded with the encoding requested by the listener
+    pub data: String,
     pub sequence_number: Option<u64>,
 }
 
-impl From<GossipsubMessage> for PubsubMessage {
-    fn from(message: GossipsubMessage) -> Self {
+impl PubsubMessage {
+    pub fn new(message: GossipsubMessage, encoding: Encoding) -> Self {
         Self {
-            message_id: message.message_id,
+            message_id: message.message_id.to_string(),
             propagated_source: message.propagated_source,
             source: message.source,
-            data: message.data,
+            data: encoding.encode(&message.data),
             sequence_number: message.sequence_number,
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/subscribe",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:write"])),
+    request_body = SubscribeParam,
+    responses((status = 200, description = "Subscribed to the topic", body = ApiResponse<Empty>))
+)]
 pub async fn subscribe(
     State(connexa): State<Connexa>,
     Json(param): Json<SubscribeParam>,
-) -> Json<Value> {
-    match connexa.gossipsub().subscribe(param.topic).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    node::subscribe(&connexa, param.topic).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn unsubscribe(Path(topic): Path<String>, State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.gossipsub().unsubscribe(topic).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+#[utoipa::path(
+    delete,
+    path = "/topic/{name}/unsubscribe",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:write"])),
+    params(("name" = String, Path, description = "Name of the topic")),
+    responses((status = 200, description = "Unsubscribed from the topic", body = ApiResponse<Empty>))
+)]
+pub async fn unsubscribe(
+    Path(topic): Path<String>,
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.gossipsub().unsubscribe(topic).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = gossipsub::PublishResponse)]
+pub struct PublishResponse {
+    pub message_id: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/topic/{name}/publish",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:write"])),
+    params(("name" = String, Path, description = "Name of the topic")),
+    request_body(content(
+        (Vec<u8> = "application/octet-stream"),
+        (String = "text/plain"),
+        (PublishParam = "application/json"),
+    )),
+    responses((status = 200, description = "Message published to the topic, returning the id of the message", body = ApiResponse<PublishResponse>))
+)]
 pub async fn publish(
     Path(topic): Path<String>,
     State(connexa): State<Connexa>,
-    Json(bytes): Json<Bytes>,
-) -> Json<Value> {
-    match connexa.gossipsub().publish(topic, bytes).await {
-        Ok(_) => Json(serde_json::json!({ "status": 200 })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+    Payload(bytes): Payload,
+) -> Result<Json<ApiResponse<PublishResponse>>, ApiError> {
+    let message_id = node::publish(&connexa, IdentTopic::new(topic).hash(), bytes).await?;
+    Ok(ApiResponse::ok(PublishResponse {
+        message_id: message_id.to_string(),
+    }))
 }
 
-pub async fn peers(Path(topic): Path<String>, State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.gossipsub().peers(topic).await {
-        Ok(peers) => Json(serde_json::json!({ "status": 200, "peers": peers })),
-        Err(e) => Json(serde_json::json!({ "status": 500, "error": e.to_string() })),
-    }
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = gossipsub::PeersResponse)]
+pub struct PeersResponse {
+    #[schema(value_type = Vec<String>)]
+    pub peers: Vec<PeerId>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/topic/{name}/peers",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:read"])),
+    params(("name" = String, Path, description = "Name of the topic")),
+    responses((status = 200, description = "Peers subscribed to the topic", body = ApiResponse<PeersResponse>))
+)]
+pub async fn peers(
+    Path(topic): Path<String>,
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<PeersResponse>>, ApiError> {
+    let peers = connexa.gossipsub().peers(topic).await?;
+    Ok(ApiResponse::ok(PeersResponse { peers }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topic/{name}",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:read"])),
+    params(("name" = String, Path, description = "Name of the topic"), EncodingParam),
+    responses((status = 200, description = "Stream of events for the topic", content_type = "text/event-stream", body = PubsubEvent))
+)]
 pub async fn topic_listener(
     Path(topic): Path<String>,
+    Query(param): Query<EncodingParam>,
     State(connexa): State<Connexa>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut st = connexa
-        .gossipsub()
-        .listener(topic)
-        .await
-        .unwrap_or(futures::stream::empty().boxed());
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut st = connexa.gossipsub().listener(topic).await?;
 
-    Sse::new(async_stream::try_stream! {
+    Ok(Sse::new(async_stream::try_stream! {
         while let Some(ev) = st.next().await {
-            let event = PubsubEvent::from(ev);
+            let event = PubsubEvent::new(ev, param.encoding);
             if let Ok(event) = Event::default().json_data(event) {
                 yield event;
             }
         }
-    })
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/topic/{name}/validate",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:read"])),
+    params(("name" = String, Path, description = "Name of the topic"), EncodingParam),
+    responses((status = 200, description = "Stream of messages on the topic held until they are validated", content_type = "text/event-stream", body = PubsubMessage))
+)]
+pub async fn validator(
+    Path(topic): Path<String>,
+    Query(param): Query<EncodingParam>,
+    State(connexa): State<Connexa>,
+    State(validation): State<Validation>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    if !validation.enabled {
+        return Err(ApiError::new(
+            ErrorCode::ProtocolDisabled,
+            "message validation is not enabled",
+        ));
+    }
+
+    let topic = IdentTopic::new(topic).hash();
+    let mut st = connexa.gossipsub().listener(topic.clone()).await?;
+    let guard = validation.register(topic);
+
+    Ok(Sse::new(async_stream::try_stream! {
+        let _guard = guard;
+        while let Some(ev) = st.next().await {
+            let GossipsubEvent::Message { message } = ev else {
+                continue;
+            };
+            validation.hold(message.message_id.clone(), message.propagated_source);
+            if let Ok(event) = Event::default().json_data(PubsubMessage::new(message, param.encoding)) {
+                yield event;
+            }
+        }
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Acceptance {
+    /// Forward the message to other peers
+    Accept,
+    /// Drop the message as invalid without forwarding it, penalizing the peer that propagated it
+    /// when peer scoring is enabled
+    Reject,
+    /// Drop the message without forwarding it
+    Ignore,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ValidateParam {
+    acceptance: Acceptance,
+}
+
+#[utoipa::path(
+    post,
+    path = "/validate/{message_id}",
+    tag = "gossipsub",
+    security(("bearer" = ["gossipsub:write"])),
+    params(("message_id" = String, Path, description = "Id of the message encoded in hex")),
+    request_body = ValidateParam,
+    responses((status = 200, description = "Validation result reported", body = ApiResponse<Empty>))
+)]
+pub async fn validate(
+    Path(message_id): Path<String>,
+    State(connexa): State<Connexa>,
+    State(validation): State<Validation>,
+    Json(param): Json<ValidateParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    let not_found = || {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!("message {message_id} not found"),
+        )
+    };
+
+    let (propagation_source, id) = validation.take(&message_id).ok_or_else(not_found)?;
+
+    let acceptance = match param.acceptance {
+        Acceptance::Accept => MessageAcceptance::Accept,
+        Acceptance::Reject => MessageAcceptance::Reject,
+        Acceptance::Ignore => MessageAcceptance::Ignore,
+    };
+
+    // The message would no longer be in the cache if it was held for too long
+    if !connexa
+        .gossipsub()
+        .report_message(propagation_source, id, acceptance)
+        .await?
+    {
+        return Err(not_found());
+    }
+
+    Ok(ApiResponse::ok(Empty {}))
 }
@@ -0,0 +1,164 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json};
+use axum::extract::{Path, State};
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::identify::Info;
+use connexa::prelude::{Multiaddr, PeerId};
+use futures::Stream;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Identify info received from connected peers, along with listeners of identify events
+#[derive(Clone)]
+pub struct Identify {
+    enabled: bool,
+    peers: Arc<Mutex<HashMap<PeerId, PeerInfo>>>,
+    events: broadcast::Sender<IdentifyEvent>,
+}
+
+impl Identify {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            peers: Arc::default(),
+            events,
+        }
+    }
+
+    /// Cache the info received from the peer
+    pub fn received(&self, peer_id: PeerId, info: &Info) {
+        let info = PeerInfo::new(peer_id, info);
+        self.peers
+            .lock()
+            .expect("not poisoned")
+            .insert(peer_id, info.clone());
+        _ = self.events.send(IdentifyEvent::Received { info });
+    }
+
+    /// Notify listeners that the info of the node was pushed to the peer
+    pub fn pushed(&self, peer_id: PeerId, local_peer_id: PeerId, info: &Info) {
+        _ = self.events.send(IdentifyEvent::Pushed {
+            peer_id,
+            info: PeerInfo::new(local_peer_id, info),
+        });
+    }
+
+    /// Forget the info of the peer once it is no longer connected
+    pub fn remove(&self, peer_id: &PeerId) {
+        self.peers.lock().expect("not poisoned").remove(peer_id);
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "identify")
+    }
+
+    pub fn get(&self, peer_id: &PeerId) -> Option<PeerInfo> {
+        self.peers
+            .lock()
+            .expect("not poisoned")
+            .get(peer_id)
+            .cloned()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct PeerInfo {
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    pub protocol_version: String,
+    pub agent_version: String,
+    pub protocols: Vec<String>,
+    #[schema(value_type = Vec<String>)]
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Address of the node as observed by the peer
+    #[schema(value_type = String)]
+    pub observed_addr: Multiaddr,
+    /// Seconds since the unix epoch when the info was received or pushed
+    pub timestamp: u64,
+}
+
+impl PeerInfo {
+    fn new(peer_id: PeerId, info: &Info) -> Self {
+        Self {
+            peer_id,
+            protocol_version: info.protocol_version.clone(),
+            agent_version: info.agent_version.clone(),
+            protocols: info.protocols.iter().map(ToString::to_string).collect(),
+            listen_addrs: info.listen_addrs.clone(),
+            observed_addr: info.observed_addr.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IdentifyEvent {
+    /// Info received from a peer
+    Received { info: PeerInfo },
+    /// Info of the node pushed to the peer
+    Pushed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        info: PeerInfo,
+    },
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = identify::InfoResponse)]
+pub struct InfoResponse {
+    pub info: PeerInfo,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{peer_id}",
+    tag = "identify",
+    security(("bearer" = ["identify:read"])),
+    params(("peer_id" = String, Path, description = "Connected peer")),
+    responses((status = 200, description = "Identify info received from the peer", body = ApiResponse<InfoResponse>))
+)]
+pub async fn info(
+    Path(peer_id): Path<String>,
+    State(identify): State<Identify>,
+) -> Result<Json<ApiResponse<InfoResponse>>, ApiError> {
+    identify.ensure_enabled()?;
+
+    let peer_id: PeerId = peer_id
+        .parse()
+        .map_err(|_| ApiError::invalid_request("invalid peer id"))?;
+
+    let Some(info) = identify.get(&peer_id) else {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("identify info for {peer_id} not found"),
+        ));
+    };
+
+    Ok(ApiResponse::ok(InfoResponse { info }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "identify",
+    security(("bearer" = ["identify:read"])),
+    responses((status = 200, description = "Stream of identify info received from or pushed to peers", content_type = "text/event-stream", body = IdentifyEvent))
+)]
+pub async fn listener(
+    State(identify): State<Identify>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    identify.ensure_enabled()?;
+
+    Ok(routes::event_stream(identify.events.subscribe()))
+}
@@ -1,134 +1,309 @@
+use crate::config::kademlia::Mode as ConfigMode;
+use crate::error::{ApiError, ErrorCode};
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Empty, Json, Query};
+use axum::extract::{Path, State};
 use axum::response::Sse;
 use axum::response::sse::Event;
-use axum::{Json, extract::State};
 use connexa::prelude::DHTEvent;
-use connexa::prelude::dht::{PeerRecord, ProviderRecord, Quorum, Record};
-use connexa::{
-    handle::Connexa,
-    prelude::{Multiaddr, PeerId},
-};
+use connexa::prelude::dht::{Mode, PeerRecord, ProviderRecord, Quorum, Record};
+use connexa::prelude::{Multiaddr, PeerId};
+use futures::channel::oneshot;
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::num::NonZeroUsize;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+/// Duration a record would be held for confirmation before it is dropped without being stored
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Mode requested for the node, since connexa only reports the mode the node is operating in,
+/// along with the records awaiting confirmation when record filtering is enabled
+#[derive(Clone)]
+pub struct Kademlia {
+    mode: Arc<Mutex<KadMode>>,
+    pending: Arc<Mutex<PendingRecords>>,
+}
+
+#[derive(Default)]
+struct PendingRecords {
+    next_id: u64,
+    records: HashMap<u64, PendingRecord>,
+}
+
+struct PendingRecord {
+    confirm: Confirm,
+    expires: Instant,
+}
+
+/// Record along with the channel used to store it
+enum Confirm {
+    Record(Record, oneshot::Sender<std::io::Result<Record>>),
+    Provider(
+        ProviderRecord,
+        oneshot::Sender<std::io::Result<ProviderRecord>>,
+    ),
+}
+
+impl Confirm {
+    /// Store the record, or drop it if `store` is false. Returns false if the node no longer
+    /// awaits the confirmation
+    fn send(self, store: bool) -> bool {
+        let rejected = || std::io::Error::other("record rejected");
+        match self {
+            Confirm::Record(record, tx) => tx
+                .send(store.then_some(record).ok_or_else(rejected))
+                .is_ok(),
+            Confirm::Provider(record, tx) => tx
+                .send(store.then_some(record).ok_or_else(rejected))
+                .is_ok(),
+        }
+    }
+}
+
+impl Kademlia {
+    pub fn new(mode: ConfigMode) -> Self {
+        let mode = match mode {
+            ConfigMode::Auto => KadMode::Auto,
+            ConfigMode::Client => KadMode::Client,
+            ConfigMode::Server => KadMode::Server,
+        };
+        Self {
+            mode: Arc::new(Mutex::new(mode)),
+            pending: Arc::default(),
+        }
+    }
+
+    /// Hold the confirmation of the record, returning the id used to confirm it. Records are
+    /// only awaiting confirmation when record filtering is enabled
+    fn hold(&self, event: &mut DHTEvent) -> Option<u64> {
+        let confirm = match event {
+            DHTEvent::PutRecord { record, .. } => {
+                Confirm::Record(record.record.clone()?, record.confirm.take()?)
+            }
+            DHTEvent::ProvideRecord { record } => {
+                Confirm::Provider(record.record.clone()?, record.confirm.take()?)
+            }
+        };
+
+        let now = Instant::now();
+        let mut pending = self.pending.lock().expect("not poisoned");
+        pending.records.retain(|_, record| record.expires > now);
+        let id = pending.next_id;
+        pending.next_id += 1;
+        pending.records.insert(
+            id,
+            PendingRecord {
+                confirm,
+                expires: now + CONFIRM_TIMEOUT,
+            },
+        );
+        Some(id)
+    }
+
+    fn take(&self, id: u64) -> Option<Confirm> {
+        let mut pending = self.pending.lock().expect("not poisoned");
+        pending
+            .records
+            .remove(&id)
+            .filter(|record| record.expires > Instant::now())
+            .map(|record| record.confirm)
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct FindPeerParam {
+    #[schema(value_type = String)]
     peer_id: PeerId,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct RecordKeyParam {
     key: String,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct FindPeerResponse {
+    /// Addresses of every peer found
+    #[schema(value_type = HashMap<String, Vec<String>>)]
+    list: HashMap<PeerId, Vec<Multiaddr>>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/find_peer",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:read"])),
+    request_body = FindPeerParam,
+    responses((status = 200, description = "Addresses of the peers found", body = ApiResponse<FindPeerResponse>))
+)]
 pub async fn find_peer(
     State(connexa): State<Connexa>,
     Json(param): Json<FindPeerParam>,
-) -> Json<Value> {
-    match connexa.dht().find_peer(param.peer_id).await {
-        Ok(info) => {
-            let map: HashMap<PeerId, Vec<Multiaddr>> = info
-                .into_iter()
-                .map(|info| (info.peer_id, info.addrs))
-                .collect();
-
-            let info = serde_json::to_value(map).expect("correct serialization");
-
-            Json(serde_json::json!({
-                "status": 200,
-                "list": info,
-            }))
-        }
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+) -> Result<Json<ApiResponse<FindPeerResponse>>, ApiError> {
+    let info = connexa.dht().find_peer(param.peer_id).await?;
+    let map: HashMap<PeerId, Vec<Multiaddr>> = info
+        .into_iter()
+        .map(|info| (info.peer_id, info.addrs))
+        .collect();
+
+    Ok(ApiResponse::ok(FindPeerResponse { list: map }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/provide",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    request_body = RecordKeyParam,
+    responses((status = 200, description = "Providing the key", body = ApiResponse<Empty>))
+)]
 pub async fn provide(
     State(connexa): State<Connexa>,
     Json(param): Json<RecordKeyParam>,
-) -> Json<Value> {
-    match connexa.dht().provide(param.key).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.dht().provide(param.key).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/stop_provide",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    request_body = RecordKeyParam,
+    responses((status = 200, description = "No longer providing the key", body = ApiResponse<Empty>))
+)]
 pub async fn stop_provide(
     State(connexa): State<Connexa>,
     Json(param): Json<RecordKeyParam>,
-) -> Json<Value> {
-    match connexa.dht().stop_provide(param.key).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.dht().stop_provide(param.key).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn bootstrap(State(connexa): State<Connexa>) -> Json<Value> {
+#[utoipa::path(
+    post,
+    path = "/bootstrap",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    responses((status = 200, description = "Bootstrapping in the background", body = ApiResponse<Empty>))
+)]
+pub async fn bootstrap(
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
     // we use lazy because we dont want have the http client having to wait until bootstrapping
     // completes
-    match connexa.dht().bootstrap_lazy().await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+    connexa.dht().bootstrap_lazy().await?;
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KadMode {
+    /// Switch between client and server based on whether the node has a confirmed external address
+    Auto,
+    Client,
+    Server,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+pub struct ModeParam {
+    mode: KadMode,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModeResponse {
+    /// Mode set for the node
+    mode: KadMode,
+    /// Mode the node is operating in, being either `client` or `server`. This only differs from
+    /// `mode` when it is `auto`
+    current: KadMode,
+}
+
+#[utoipa::path(
+    get,
+    path = "/mode",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:read"])),
+    responses((status = 200, description = "Mode set for the node along with the mode it is operating in", body = ApiResponse<ModeResponse>))
+)]
+pub async fn mode(
+    State(connexa): State<Connexa>,
+    State(kademlia): State<Kademlia>,
+) -> Result<Json<ApiResponse<ModeResponse>>, ApiError> {
+    let current = match connexa.dht().mode().await? {
+        Mode::Client => KadMode::Client,
+        Mode::Server => KadMode::Server,
+    };
+    let mode = *kademlia.mode.lock().expect("not poisoned");
+    Ok(ApiResponse::ok(ModeResponse { mode, current }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mode",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    request_body = ModeParam,
+    responses((status = 200, description = "Mode of the node set", body = ApiResponse<Empty>))
+)]
+pub async fn set_mode(
+    State(connexa): State<Connexa>,
+    State(kademlia): State<Kademlia>,
+    Json(param): Json<ModeParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    let mode = match param.mode {
+        KadMode::Auto => None,
+        KadMode::Client => Some(Mode::Client),
+        KadMode::Server => Some(Mode::Server),
+    };
+    connexa.dht().set_mode(mode).await?;
+    *kademlia.mode.lock().expect("not poisoned") = param.mode;
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct AddAddressParam {
+    #[schema(value_type = String)]
     peer_id: PeerId,
+    #[schema(value_type = String)]
     address: Multiaddr,
 }
 
+#[utoipa::path(
+    post,
+    path = "/add_address",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    request_body = AddAddressParam,
+    responses((status = 200, description = "Address added to the routing table", body = ApiResponse<Empty>))
+)]
 pub async fn add_address(
     State(connexa): State<Connexa>,
     Json(param): Json<AddAddressParam>,
-) -> Json<Value> {
-    // we use lazy because we dont want have the http client having to wait until bootstrapping
-    // completes
-    match connexa
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa
         .dht()
         .add_address(param.peer_id, param.address)
-        .await
-    {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+        .await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PutRecordParam {
     key: String,
     data: Vec<u8>,
+    /// Number of peers that must store the record
     qourum: PutRecordQuorum,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum PutRecordQuorum {
     One,
@@ -152,24 +327,28 @@ impl From<PutRecordQuorum> for Quorum {
     }
 }
 
-pub async fn put(State(connexa): State<Connexa>, Json(param): Json<PutRecordParam>) -> Json<Value> {
-    match connexa
+#[utoipa::path(
+    post,
+    path = "/put",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    request_body = PutRecordParam,
+    responses((status = 200, description = "Record stored", body = ApiResponse<Empty>))
+)]
+pub async fn put(
+    State(connexa): State<Connexa>,
+    Json(param): Json<PutRecordParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa
         .dht()
         .put(param.key, param.data, param.qourum.into())
-        .await
-    {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+        .await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct KadPeerRecord {
+    #[schema(value_type = Option<String>)]
     pub peer_id: Option<PeerId>,
     pub record: KadRecord,
 }
@@ -183,67 +362,86 @@ impl From<PeerRecord> for KadPeerRecord {
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/get",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:read"])),
+    params(RecordKeyParam),
+    responses((status = 200, description = "Stream of records found for the key", content_type = "text/event-stream", body = KadPeerRecord))
+)]
 pub async fn get(
     State(connexa): State<Connexa>,
-    Json(param): Json<RecordKeyParam>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut st = connexa
-        .dht()
-        .get(param.key)
-        .await
-        .unwrap_or(futures::stream::empty().boxed());
+    Query(param): Query<RecordKeyParam>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut st = connexa.dht().get(param.key).await?;
 
-    Sse::new(async_stream::try_stream! {
+    Ok(Sse::new(async_stream::try_stream! {
         while let Some(Ok(event)) = st.next().await {
             let event = KadPeerRecord::from(event);
             if let Ok(event) = Event::default().json_data(event) {
                 yield event;
             }
         }
-    })
+    }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/get_providers",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:read"])),
+    params(RecordKeyParam),
+    responses((status = 200, description = "Stream of providers found for the key", content_type = "text/event-stream", body = Vec<String>))
+)]
 pub async fn get_providers(
     State(connexa): State<Connexa>,
-    Json(param): Json<RecordKeyParam>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut st = connexa
-        .dht()
-        .get_providers(param.key)
-        .await
-        .unwrap_or(futures::stream::empty().boxed());
+    Query(param): Query<RecordKeyParam>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut st = connexa.dht().get_providers(param.key).await?;
 
-    Sse::new(async_stream::try_stream! {
+    Ok(Sse::new(async_stream::try_stream! {
         while let Some(Ok(event)) = st.next().await {
             if let Ok(event) = Event::default().json_data(event) {
                 yield event;
             }
         }
-    })
+    }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct OptionalRecordKeyParam {
+    /// Only receive events for this key
     key: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum KadEvent {
     PutRecord {
+        #[schema(value_type = String)]
         source: PeerId,
         record: Option<KadRecord>,
+        /// Id used to confirm the record with `POST /kademlia/records/{id}`. Only set when record
+        /// filtering is enabled
+        id: Option<u64>,
     },
     ProvideRecord {
         record: Option<KadProviderRecord>,
+        /// Id used to confirm the record with `POST /kademlia/records/{id}`. Only set when record
+        /// filtering is enabled
+        id: Option<u64>,
     },
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct KadRecord {
     pub key: Vec<u8>,
     pub value: Vec<u8>,
+    #[schema(value_type = Option<String>)]
     pub publisher: Option<PeerId>,
+    /// Seconds until the record expires
     pub expires: Option<i64>,
 }
 
@@ -260,11 +458,14 @@ impl From<Record> for KadRecord {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct KadProviderRecord {
     pub key: Vec<u8>,
+    #[schema(value_type = String)]
     pub provider: PeerId,
+    /// Seconds until the record expires
     pub expires: Option<i64>,
+    #[schema(value_type = Vec<String>)]
     pub addresses: Vec<Multiaddr>,
 }
 
@@ -281,36 +482,114 @@ impl From<ProviderRecord> for KadProviderRecord {
     }
 }
 
-impl From<DHTEvent> for KadEvent {
-    fn from(ev: DHTEvent) -> Self {
+impl KadEvent {
+    fn new(ev: DHTEvent, id: Option<u64>) -> Self {
         match ev {
             DHTEvent::PutRecord { source, record } => KadEvent::PutRecord {
                 source,
                 record: record.record.map(Into::into),
+                id,
             },
             DHTEvent::ProvideRecord { record } => KadEvent::ProvideRecord {
                 record: record.record.map(Into::into),
+                id,
             },
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:read"])),
+    params(OptionalRecordKeyParam),
+    responses((status = 200, description = "Stream of records and provider records stored by other peers", content_type = "text/event-stream", body = KadEvent))
+)]
 pub async fn listener(
     State(connexa): State<Connexa>,
-    Json(param): Json<OptionalRecordKeyParam>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut st = connexa
-        .dht()
-        .listener(param.key)
-        .await
-        .unwrap_or(futures::stream::empty().boxed());
+    State(kademlia): State<Kademlia>,
+    Query(param): Query<OptionalRecordKeyParam>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let mut st = connexa.dht().listener(param.key).await?;
 
-    Sse::new(async_stream::try_stream! {
-        while let Some(event) = st.next().await {
-            let ev = KadEvent::from(event);
+    Ok(Sse::new(async_stream::try_stream! {
+        while let Some(mut event) = st.next().await {
+            let id = kademlia.hold(&mut event);
+            let ev = KadEvent::new(event, id);
             if let Ok(event) = Event::default().json_data(ev) {
                 yield event;
             }
         }
-    })
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ConfirmParam {
+    /// Store the record, or drop it if false
+    store: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/records/{id}",
+    tag = "kademlia",
+    security(("bearer" = ["kademlia:write"])),
+    params(("id" = u64, Path, description = "Id of the record streamed from `GET /kademlia`")),
+    request_body = ConfirmParam,
+    responses((status = 200, description = "Record stored or dropped", body = ApiResponse<Empty>))
+)]
+pub async fn confirm(
+    Path(id): Path<u64>,
+    State(kademlia): State<Kademlia>,
+    Json(param): Json<ConfirmParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    let not_found = || ApiError::new(ErrorCode::NotFound, format!("record {id} not found"));
+
+    let confirm = kademlia.take(id).ok_or_else(not_found)?;
+    if !confirm.send(param.store) {
+        return Err(not_found());
+    }
+
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connexa::prelude::RecordHandle;
+    use connexa::prelude::dht::RecordKey;
+
+    #[tokio::test]
+    async fn confirmed_records_are_sent_back() {
+        let kademlia = Kademlia::new(ConfigMode::Auto);
+        let record = Record::new(RecordKey::new(&"key"), b"value".to_vec());
+
+        let (tx, rx) = oneshot::channel();
+        let mut event = DHTEvent::PutRecord {
+            source: PeerId::random(),
+            record: RecordHandle {
+                record: Some(record.clone()),
+                confirm: Some(tx),
+            },
+        };
+        let id = kademlia
+            .hold(&mut event)
+            .expect("record awaits confirmation");
+
+        assert!(kademlia.take(id).expect("record is held").send(true));
+        assert_eq!(rx.await.unwrap().unwrap(), record);
+        assert!(kademlia.take(id).is_none());
+
+        // Without a confirmation channel, which is the case without record filtering, nothing
+        // is held
+        let mut event = DHTEvent::PutRecord {
+            source: PeerId::random(),
+            record: RecordHandle {
+                record: Some(record),
+                confirm: None,
+            },
+        };
+        assert!(kademlia.hold(&mut event).is_none());
+    }
 }
@@ -0,0 +1,149 @@
+use crate::error::ApiError;
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json};
+use axum::extract::State;
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::{Multiaddr, PeerId};
+use futures::Stream;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Peers discovered on the local network, along with listeners of discovery events
+#[derive(Clone)]
+pub struct Mdns {
+    enabled: bool,
+    peers: Arc<Mutex<HashMap<PeerId, MdnsPeer>>>,
+    events: broadcast::Sender<MdnsEvent>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct MdnsPeer {
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Multiaddr>,
+    /// Seconds since the unix epoch when the peer was last discovered
+    pub last_seen: u64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MdnsEvent {
+    /// The peer was discovered on the local network at the addresses
+    Discovered {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        #[schema(value_type = Vec<String>)]
+        addresses: Vec<Multiaddr>,
+    },
+    /// The addresses of the peer were not refreshed in time and expired
+    Expired {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        #[schema(value_type = Vec<String>)]
+        addresses: Vec<Multiaddr>,
+    },
+}
+
+impl Mdns {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            peers: Arc::default(),
+            events,
+        }
+    }
+
+    pub fn discovered(&self, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        {
+            let mut peers = self.peers.lock().expect("not poisoned");
+            let peer = peers.entry(peer_id).or_insert_with(|| MdnsPeer {
+                peer_id,
+                addresses: vec![],
+                last_seen: 0,
+            });
+            for address in &addresses {
+                if !peer.addresses.contains(address) {
+                    peer.addresses.push(address.clone());
+                }
+            }
+            peer.last_seen = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+        }
+
+        _ = self
+            .events
+            .send(MdnsEvent::Discovered { peer_id, addresses });
+    }
+
+    /// Remove the expired addresses, forgetting the peer once none of its addresses remain
+    pub fn expired(&self, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        {
+            let mut peers = self.peers.lock().expect("not poisoned");
+            if let Some(peer) = peers.get_mut(&peer_id) {
+                peer.addresses
+                    .retain(|address| !addresses.contains(address));
+                if peer.addresses.is_empty() {
+                    peers.remove(&peer_id);
+                }
+            }
+        }
+
+        _ = self.events.send(MdnsEvent::Expired { peer_id, addresses });
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "mdns")
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = mdns::PeersResponse)]
+pub struct PeersResponse {
+    pub peers: Vec<MdnsPeer>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/peers",
+    tag = "mdns",
+    security(("bearer" = ["mdns:read"])),
+    responses((status = 200, description = "Peers discovered on the local network", body = ApiResponse<PeersResponse>))
+)]
+pub async fn peers(State(mdns): State<Mdns>) -> Result<Json<ApiResponse<PeersResponse>>, ApiError> {
+    mdns.ensure_enabled()?;
+
+    let mut peers = mdns
+        .peers
+        .lock()
+        .expect("not poisoned")
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    peers.sort_by_key(|peer| std::cmp::Reverse(peer.last_seen));
+
+    Ok(ApiResponse::ok(PeersResponse { peers }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "mdns",
+    security(("bearer" = ["mdns:read"])),
+    responses((status = 200, description = "Stream of peers discovered or expired", content_type = "text/event-stream", body = MdnsEvent))
+)]
+pub async fn listener(
+    State(mdns): State<Mdns>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    mdns.ensure_enabled()?;
+
+    Ok(routes::event_stream(mdns.events.subscribe()))
+}
@@ -1,8 +1,292 @@
+pub mod autonat;
 pub mod blacklist;
+pub mod dcutr;
 pub mod floodsub;
 pub mod gossipsub;
+pub mod identify;
 pub mod kademlia;
+pub mod mdns;
 pub mod peerstore;
+pub mod ping;
+pub mod pubsub;
+pub mod relay;
 pub mod rendezvous;
+pub mod request_response;
+pub mod stream;
 pub mod swarm;
+pub mod upnp;
 pub mod whitelist;
+
+use crate::error::{ApiError, ErrorCode};
+use crate::openapi;
+use crate::state::AppState;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, FromRequest, FromRequestParts, Request};
+use axum::response::sse::Event;
+use axum::response::{IntoResponse, Response, Sse};
+use connexa::prelude::{Multiaddr, PeerId};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+/// Number of events buffered for each subscriber of a broadcast before older events are dropped
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Body of every successful response, serialized as `{ "status": 200, ... }` where the fields of
+/// the body follow the status
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ApiResponse<T> {
+    status: u16,
+    #[serde(flatten)]
+    body: T,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn ok(body: T) -> Json<Self> {
+        Json(Self { status: 200, body })
+    }
+}
+
+/// Response without any fields other than the status
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Empty {}
+
+/// Peer along with its addresses, serialized as `[peer_id, [address, ...]]`
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PeerAddresses(
+    #[schema(value_type = String)] pub PeerId,
+    #[schema(value_type = Vec<String>)] pub Vec<Multiaddr>,
+);
+
+/// Every route of the api nested under the group of the route, along with the specification
+/// documenting them. Requests to request response routes are limited to `request_response_limit`
+/// bytes
+pub fn router(request_response_limit: usize) -> OpenApiRouter<AppState> {
+    let gossipsub_routes = OpenApiRouter::new()
+        .routes(routes!(gossipsub::subscribe))
+        .routes(routes!(gossipsub::topic_listener))
+        .routes(routes!(gossipsub::unsubscribe))
+        .routes(routes!(gossipsub::peers))
+        .routes(routes!(gossipsub::publish))
+        .routes(routes!(gossipsub::validator))
+        .routes(routes!(gossipsub::validate));
+
+    let floodsub_routes = OpenApiRouter::new()
+        .routes(routes!(floodsub::subscribe))
+        .routes(routes!(floodsub::topic_listener))
+        .routes(routes!(floodsub::unsubscribe))
+        .routes(routes!(floodsub::publish))
+        .routes(routes!(floodsub::add_peer))
+        .routes(routes!(floodsub::remove_peer));
+
+    let kad_routes = OpenApiRouter::new()
+        .routes(routes!(kademlia::listener))
+        .routes(routes!(kademlia::find_peer))
+        .routes(routes!(kademlia::provide))
+        .routes(routes!(kademlia::stop_provide))
+        .routes(routes!(kademlia::get_providers))
+        .routes(routes!(kademlia::bootstrap))
+        .routes(routes!(kademlia::get))
+        .routes(routes!(kademlia::put))
+        .routes(routes!(kademlia::add_address))
+        .routes(routes!(kademlia::mode, kademlia::set_mode))
+        .routes(routes!(kademlia::confirm));
+
+    let identify_routes = OpenApiRouter::new()
+        .routes(routes!(identify::listener))
+        .routes(routes!(identify::info));
+
+    let ping_routes = OpenApiRouter::new()
+        .routes(routes!(ping::listener))
+        .routes(routes!(ping::ping));
+
+    let autonat_routes = OpenApiRouter::new()
+        .routes(routes!(autonat::listener))
+        .routes(routes!(autonat::status));
+
+    let relay_routes = OpenApiRouter::new()
+        .routes(routes!(relay::listener))
+        .routes(routes!(relay::reserve, relay::reservations))
+        .routes(routes!(relay::cancel))
+        .routes(routes!(relay::server_stats));
+
+    let dcutr_routes = OpenApiRouter::new()
+        .routes(routes!(dcutr::listener))
+        .routes(routes!(dcutr::upgrade));
+
+    let upnp_routes = OpenApiRouter::new()
+        .routes(routes!(upnp::listener))
+        .routes(routes!(upnp::status));
+
+    let mdns_routes = OpenApiRouter::new()
+        .routes(routes!(mdns::listener))
+        .routes(routes!(mdns::peers));
+
+    let stream_routes = OpenApiRouter::new()
+        .routes(routes!(
+            stream::register,
+            stream::protocols,
+            stream::unregister
+        ))
+        .routes(routes!(stream::accept))
+        .routes(routes!(stream::open));
+
+    let rz_routes = OpenApiRouter::new()
+        .routes(routes!(rendezvous::register))
+        .routes(routes!(rendezvous::unregister))
+        .routes(routes!(rendezvous::discovery));
+
+    let rr_routes = OpenApiRouter::new()
+        .routes(routes!(request_response::send))
+        .routes(routes!(request_response::requests))
+        .routes(routes!(request_response::respond))
+        .layer(DefaultBodyLimit::max(request_response_limit));
+
+    let blacklist_route = OpenApiRouter::new()
+        .routes(routes!(blacklist::add))
+        .routes(routes!(blacklist::remove))
+        .routes(routes!(blacklist::list));
+
+    let whitelist_route = OpenApiRouter::new()
+        .routes(routes!(whitelist::add))
+        .routes(routes!(whitelist::remove))
+        .routes(routes!(whitelist::list));
+
+    let peerstore_route = OpenApiRouter::new()
+        .routes(routes!(peerstore::add))
+        .routes(routes!(peerstore::remove))
+        .routes(routes!(peerstore::list));
+
+    let swarm_route = OpenApiRouter::new()
+        .routes(routes!(swarm::dial))
+        .routes(routes!(swarm::disconnect))
+        .routes(routes!(swarm::is_connected))
+        .routes(routes!(swarm::connected_peers))
+        .routes(routes!(swarm::connections))
+        .routes(routes!(swarm::addresses))
+        .routes(routes!(swarm::listen_on))
+        .routes(routes!(swarm::remove_listener))
+        .routes(routes!(swarm::listeners))
+        .routes(routes!(swarm::listen_events))
+        .routes(routes!(swarm::external_addresses))
+        .routes(routes!(swarm::listening_addresses))
+        .routes(routes!(swarm::add_peer_address))
+        .routes(routes!(swarm::connection_listener));
+
+    OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .nest("/gossipsub", gossipsub_routes)
+        .nest("/floodsub", floodsub_routes)
+        .nest("/kademlia", kad_routes)
+        .nest("/identify", identify_routes)
+        .nest("/ping", ping_routes)
+        .nest("/autonat", autonat_routes)
+        .nest("/relay", relay_routes)
+        .nest("/dcutr", dcutr_routes)
+        .nest("/upnp", upnp_routes)
+        .nest("/mdns", mdns_routes)
+        .nest("/stream", stream_routes)
+        .nest("/rendezvous", rz_routes)
+        .nest("/request_response", rr_routes)
+        .nest("/blacklist", blacklist_route)
+        .nest("/whitelist", whitelist_route)
+        .nest("/peerstore", peerstore_route)
+        .nest("/swarm", swarm_route)
+}
+
+/// Reject requests to routes of a protocol that is not enabled
+pub fn ensure_enabled(enabled: bool, protocol: &str) -> Result<(), ApiError> {
+    match enabled {
+        true => Ok(()),
+        false => Err(ApiError::new(
+            ErrorCode::ProtocolDisabled,
+            format!("{protocol} is not enabled"),
+        )),
+    }
+}
+
+/// Json extractor and response that would reject invalid bodies with an [`ApiError`]
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Query extractor that would reject invalid parameters with an [`ApiError`]
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// Raw body extractor that would reject invalid bodies with an [`ApiError`]
+pub struct Body(pub Bytes);
+
+impl<S: Send + Sync> FromRequest<S> for Body {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(Bytes::from_request(req, state).await?))
+    }
+}
+
+/// Protocol of a route, taken from the query since protocols contain `/`, eg.
+/// `?protocol=/echo/1.0.0`
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProtocolParam {
+    pub protocol: String,
+}
+
+/// Stream the events received from the broadcast, skipping any events missed by a slow client
+pub fn event_stream<T: Serialize + Clone + Send + 'static>(
+    rx: broadcast::Receiver<T>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    filtered_event_stream(rx, |_| true)
+}
+
+/// Stream the events received from the broadcast that match the filter
+pub fn filtered_event_stream<T: Serialize + Clone + Send + 'static>(
+    mut rx: broadcast::Receiver<T>,
+    filter: impl Fn(&T) -> bool + Send + 'static,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(async_stream::try_stream! {
+        loop {
+            let ev = match rx.recv().await {
+                Ok(ev) => ev,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if !filter(&ev) {
+                continue;
+            }
+            if let Ok(event) = Event::default().json_data(ev) {
+                yield event;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{StatusCode, header};
+
+    #[tokio::test]
+    async fn body_rejection_is_json() {
+        let body = futures::stream::iter([Err::<Bytes, _>(std::io::Error::other("reset"))]);
+        let req = Request::new(axum::body::Body::from_stream(body));
+        let Err(err) = Body::from_request(req, &()).await else {
+            panic!("body should fail to be read");
+        };
+
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    }
+}
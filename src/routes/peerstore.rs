@@ -1,102 +1,108 @@
-use axum::Json;
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Empty, Json, PeerAddresses, Query};
 use axum::extract::State;
-use axum::http::StatusCode;
-use connexa::handle::Connexa;
 use connexa::prelude::{Multiaddr, PeerId};
-use serde::Deserialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[schema(as = peerstore::Param)]
+#[into_params(parameter_in = Query)]
 pub struct Param {
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
     peer_id: Option<PeerId>,
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
     address: Option<Multiaddr>,
 }
 
-pub async fn add(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
+#[utoipa::path(
+    post,
+    path = "/add",
+    tag = "peerstore",
+    security(("bearer" = ["peerstore:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Address added for the peer", body = ApiResponse<Empty>))
+)]
+pub async fn add(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
     let Some(peer_id) = param.peer_id else {
-        return Json(serde_json::json!({
-            "status": 500,
-            "message": "peer id is required"
-        }));
+        return Err(ApiError::invalid_request("peer id is required"));
     };
     let Some(addr) = param.address else {
-        return Json(serde_json::json!({
-            "status": 500,
-            "message": "address is required"
-        }));
+        return Err(ApiError::invalid_request("address is required"));
     };
-    match connexa.peer_store().add_address(peer_id, addr).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+    connexa.peer_store().add_address(peer_id, addr).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn remove(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
+#[utoipa::path(
+    delete,
+    path = "/remove",
+    tag = "peerstore",
+    security(("bearer" = ["peerstore:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Address, or the peer if no address is provided, removed", body = ApiResponse<Empty>))
+)]
+pub async fn remove(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
     let Some(peer_id) = param.peer_id else {
-        return Json(serde_json::json!({
-            "status": 500,
-            "message": "peer id is required"
-        }));
+        return Err(ApiError::invalid_request("peer id is required"));
     };
     match param.address {
-        Some(addr) => match connexa.peer_store().remove_address(peer_id, addr).await {
-            Ok(_) => Json(serde_json::json!({
-                "status": 200,
-            })),
-            Err(e) => Json(serde_json::json!({
-                "status": 500,
-                "message": e.to_string()
-            })),
-        },
-        None => match connexa.peer_store().remove_peer(peer_id).await {
-            Ok(_) => Json(serde_json::json!({
-                "status": 200,
-            })),
-            Err(e) => Json(serde_json::json!({
-                "status": 500,
-                "message": e.to_string()
-            })),
-        },
+        Some(addr) => connexa.peer_store().remove_address(peer_id, addr).await?,
+        None => {
+            connexa.peer_store().remove_peer(peer_id).await?;
+        }
     }
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn list(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match param.peer_id {
-        Some(peer_id) => match connexa.peer_store().list(peer_id).await {
-            Ok(peers) => {
-                let addrs: Vec<String> = peers.into_iter().map(|p| p.to_string()).collect();
-                Json(serde_json::json!({
-                    "status": 200,
-                    "addresses": addrs,
-                }))
-            }
-            Err(e) => {
-                let status = StatusCode::INTERNAL_SERVER_ERROR;
-                Json(serde_json::json!({
-                    "status": status.as_u16(),
-                    "message": e.to_string()
-                }))
-            }
-        },
-        None => match connexa.peer_store().list_all().await {
-            Ok(peers) => {
-                Json(serde_json::json!({
-                    "status": 200,
-                    "addresses": peers,
-                }))
-            }
-            Err(e) => {
-                let status = StatusCode::INTERNAL_SERVER_ERROR;
-                Json(serde_json::json!({
-                    "status": status.as_u16(),
-                    "message": e.to_string()
-                }))
-            }
-        },
-    }
+#[derive(Serialize, ToSchema)]
+#[schema(as = peerstore::ListResponse)]
+pub struct ListResponse {
+    addresses: Addresses,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum Addresses {
+    /// Addresses of the peer requested
+    #[schema(value_type = Vec<String>)]
+    Peer(Vec<Multiaddr>),
+    /// Every peer along with its addresses
+    All(Vec<PeerAddresses>),
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tag = "peerstore",
+    security(("bearer" = ["peerstore:read"])),
+    params(Param),
+    responses((status = 200, description = "Addresses of the peer, or of every peer if no peer is provided", body = ApiResponse<ListResponse>))
+)]
+pub async fn list(
+    State(connexa): State<Connexa>,
+    Query(param): Query<Param>,
+) -> Result<Json<ApiResponse<ListResponse>>, ApiError> {
+    let addresses = match param.peer_id {
+        Some(peer_id) => Addresses::Peer(connexa.peer_store().list(peer_id).await?),
+        None => Addresses::All(
+            connexa
+                .peer_store()
+                .list_all()
+                .await?
+                .into_iter()
+                .map(|(peer_id, addresses)| PeerAddresses(peer_id, addresses))
+                .collect(),
+        ),
+    };
+    Ok(ApiResponse::ok(ListResponse { addresses }))
 }
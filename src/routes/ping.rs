@@ -0,0 +1,259 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::node::Connexa;
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json, Query};
+use axum::extract::{Path, State};
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::PeerId;
+use connexa::prelude::swarm::ConnectionId;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::{IntoParams, ToSchema};
+
+/// Number of round trip times kept for each peer
+const RTT_HISTORY: usize = 10;
+
+/// Duration to wait for a fresh ping result before giving up. Connected peers are pinged every 15
+/// seconds and a ping fails after 20 seconds, so a result would be received well within this
+const FRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Latest ping results of connected peers, along with listeners of ping events
+#[derive(Clone)]
+pub struct Ping {
+    enabled: bool,
+    peers: Arc<Mutex<HashMap<PeerId, PeerPing>>>,
+    events: broadcast::Sender<PingEvent>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PeerPing {
+    /// Round trip times in milliseconds, from oldest to newest
+    rtts: VecDeque<f64>,
+    /// Error of the latest ping, if it failed
+    error: Option<String>,
+    /// Seconds since the unix epoch of the latest ping result
+    timestamp: u64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PingEvent {
+    Success {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        connection_id: String,
+        /// Round trip time in milliseconds
+        rtt: f64,
+    },
+    Failure {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        connection_id: String,
+        error: String,
+    },
+}
+
+impl PingEvent {
+    fn peer_id(&self) -> PeerId {
+        match self {
+            PingEvent::Success { peer_id, .. } | PingEvent::Failure { peer_id, .. } => *peer_id,
+        }
+    }
+}
+
+impl Ping {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            peers: Arc::default(),
+            events,
+        }
+    }
+
+    /// Record the result of a ping to the peer
+    pub fn result<E: ToString>(
+        &self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        result: &Result<Duration, E>,
+    ) {
+        let event = {
+            let mut peers = self.peers.lock().expect("not poisoned");
+            let peer = peers.entry(peer_id).or_default();
+            peer.timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            match result {
+                Ok(rtt) => {
+                    let rtt = rtt.as_secs_f64() * 1000.0;
+                    if peer.rtts.len() == RTT_HISTORY {
+                        peer.rtts.pop_front();
+                    }
+                    peer.rtts.push_back(rtt);
+                    peer.error = None;
+                    PingEvent::Success {
+                        peer_id,
+                        connection_id: connection_id.to_string(),
+                        rtt,
+                    }
+                }
+                Err(e) => {
+                    let error = e.to_string();
+                    peer.error = Some(error.clone());
+                    PingEvent::Failure {
+                        peer_id,
+                        connection_id: connection_id.to_string(),
+                        error,
+                    }
+                }
+            }
+        };
+
+        _ = self.events.send(event);
+    }
+
+    /// Forget the results of the peer once it is no longer connected
+    pub fn remove(&self, peer_id: &PeerId) {
+        self.peers.lock().expect("not poisoned").remove(peer_id);
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "ping")
+    }
+
+    fn get(&self, peer_id: &PeerId) -> Option<PeerPing> {
+        self.peers
+            .lock()
+            .expect("not poisoned")
+            .get(peer_id)
+            .cloned()
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PingParam {
+    /// Wait for the next ping of the peer instead of returning the latest results. Pings are not
+    /// sent on demand, so this could take up to the ping interval. Fails right away if the peer is
+    /// not connected
+    #[serde(default)]
+    fresh: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PingResponse {
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    /// Latest round trip time in milliseconds
+    pub rtt: Option<f64>,
+    /// Round trip times in milliseconds, from oldest to newest
+    pub rtts: Vec<f64>,
+    /// Error of the latest ping, if it failed
+    pub error: Option<String>,
+    /// Seconds since the unix epoch of the latest ping result
+    pub timestamp: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{peer_id}",
+    tag = "ping",
+    security(("bearer" = ["ping:read"])),
+    params(("peer_id" = String, Path, description = "Connected peer"), PingParam),
+    responses((status = 200, description = "Latest round trip times to the peer", body = ApiResponse<PingResponse>))
+)]
+pub async fn ping(
+    Path(peer_id): Path<String>,
+    Query(param): Query<PingParam>,
+    State(connexa): State<Connexa>,
+    State(ping): State<Ping>,
+) -> Result<Json<ApiResponse<PingResponse>>, ApiError> {
+    ping.ensure_enabled()?;
+
+    let peer_id: PeerId = peer_id
+        .parse()
+        .map_err(|_| ApiError::invalid_request("invalid peer id"))?;
+
+    if param.fresh {
+        // Subscribe before checking the connection so a result received in between is not missed
+        let mut rx = ping.events.subscribe();
+        if !connexa.swarm().is_connected(peer_id).await? {
+            return Err(ApiError::new(
+                ErrorCode::NotFound,
+                format!("{peer_id} is not connected"),
+            ));
+        }
+
+        let next = async {
+            loop {
+                match rx.recv().await {
+                    Ok(event) if event.peer_id() == peer_id => return Some(event),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        };
+
+        if tokio::time::timeout(FRESH_TIMEOUT, next).await.is_err() {
+            return Err(ApiError::new(
+                ErrorCode::Timeout,
+                format!("timed out waiting for a ping result from {peer_id}"),
+            ));
+        }
+    }
+
+    let Some(result) = ping.get(&peer_id) else {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("ping results for {peer_id} not found"),
+        ));
+    };
+
+    Ok(ApiResponse::ok(PingResponse {
+        peer_id,
+        rtt: result.rtts.back().copied(),
+        rtts: result.rtts.into(),
+        error: result.error,
+        timestamp: result.timestamp,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListenerParam {
+    /// Only receive events for this peer
+    #[param(value_type = Option<String>)]
+    peer_id: Option<PeerId>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "ping",
+    security(("bearer" = ["ping:read"])),
+    params(ListenerParam),
+    responses((status = 200, description = "Stream of ping results and failures", content_type = "text/event-stream", body = PingEvent))
+)]
+pub async fn listener(
+    Query(param): Query<ListenerParam>,
+    State(ping): State<Ping>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    ping.ensure_enabled()?;
+
+    Ok(routes::filtered_event_stream(
+        ping.events.subscribe(),
+        move |event| {
+            param
+                .peer_id
+                .is_none_or(|peer_id| event.peer_id() == peer_id)
+        },
+    ))
+}
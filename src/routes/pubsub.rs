@@ -0,0 +1,89 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::routes::Json;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::http::header;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use std::fmt::Write;
+use utoipa::{IntoParams, ToSchema};
+
+/// Message to publish, which is read based on the content type of the request.
+///
+/// `application/octet-stream` and `text/plain` bodies are published as is, while
+/// `application/json` bodies are expected to be a [`PublishParam`]
+pub struct Payload(pub Bytes);
+
+#[derive(Deserialize, ToSchema)]
+pub struct PublishParam {
+    /// Message encoded in base64
+    pub data: String,
+}
+
+impl<S: Send + Sync> FromRequest<S> for Payload {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase());
+
+        match content_type.as_deref() {
+            None | Some("application/octet-stream") | Some("text/plain") => {
+                let bytes = Bytes::from_request(req, state).await?;
+                Ok(Payload(bytes))
+            }
+            Some("application/json") => {
+                let Json(param) = Json::<PublishParam>::from_request(req, state).await?;
+                let data = STANDARD
+                    .decode(param.data.trim())
+                    .map_err(|e| ApiError::invalid_request(format!("invalid base64: {e}")))?;
+                Ok(Payload(data.into()))
+            }
+            Some(content_type) => Err(ApiError::new(
+                ErrorCode::UnsupportedMediaType,
+                format!("unsupported content type {content_type}"),
+            )),
+        }
+    }
+}
+
+/// Encoding of the data of messages sent to listeners
+#[derive(Deserialize, ToSchema, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[default]
+    Base64,
+    /// Data that is not valid utf8 would have the invalid sequences replaced
+    Utf8,
+    Hex,
+}
+
+impl Encoding {
+    pub fn encode(&self, data: &[u8]) -> String {
+        match self {
+            Encoding::Base64 => STANDARD.encode(data),
+            Encoding::Utf8 => String::from_utf8_lossy(data).into_owned(),
+            Encoding::Hex => {
+                data.iter()
+                    .fold(String::with_capacity(data.len() * 2), |mut hex, byte| {
+                        _ = write!(hex, "{byte:02x}");
+                        hex
+                    })
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams, Default)]
+#[into_params(parameter_in = Query)]
+pub struct EncodingParam {
+    /// Encoding of the message data. Defaults to base64
+    #[serde(default)]
+    #[param(inline)]
+    pub encoding: Encoding,
+}
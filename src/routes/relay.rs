@@ -0,0 +1,399 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::node::{self, Connexa};
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Empty, Json};
+use axum::extract::{Path, State};
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::transport::transport::ListenerId;
+use connexa::prelude::{Multiaddr, PeerId, Protocol};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Reservations requested on relays, along with listeners of reservation and circuit events
+#[derive(Clone)]
+pub struct Relay {
+    enabled: bool,
+    reservations: Arc<Mutex<HashMap<ListenerId, Reservation>>>,
+    events: broadcast::Sender<RelayEvent>,
+}
+
+/// Reservations and circuits served by the node as a relay
+#[derive(Clone)]
+pub struct RelayServer {
+    enabled: bool,
+    state: Arc<Mutex<ServerState>>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    reservations: HashSet<PeerId>,
+    /// Number of circuits relayed from the source to the destination peer
+    circuits: HashMap<(PeerId, PeerId), usize>,
+    reservations_denied: u64,
+    circuits_accepted: u64,
+    circuits_denied: u64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Reservation {
+    /// Id of the listener used to cancel the reservation
+    pub listener_id: String,
+    #[schema(value_type = String)]
+    pub relay_peer_id: PeerId,
+    /// Circuit address the node is listening on through the relay
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+    /// Whether the relay accepted the reservation
+    pub accepted: bool,
+    /// Seconds since the unix epoch when the reservation was last accepted or renewed
+    pub renewed_at: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RelayEvent {
+    /// A reservation was accepted or renewed by the relay
+    ReservationAccepted {
+        #[schema(value_type = String)]
+        relay_peer_id: PeerId,
+        renewal: bool,
+    },
+    /// A reservation was cancelled or could not be renewed
+    ReservationClosed {
+        listener_id: String,
+        #[schema(value_type = String)]
+        relay_peer_id: PeerId,
+        error: Option<String>,
+    },
+    /// A circuit to another peer was established through the relay
+    OutboundCircuitEstablished {
+        #[schema(value_type = String)]
+        relay_peer_id: PeerId,
+    },
+    /// A peer established a circuit to the node through a relay
+    InboundCircuitEstablished {
+        #[schema(value_type = String)]
+        src_peer_id: PeerId,
+    },
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Relay {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            reservations: Arc::default(),
+            events,
+        }
+    }
+
+    /// Record the reservation requested through the listener
+    pub fn insert(&self, listener_id: ListenerId, relay_peer_id: PeerId, address: Multiaddr) {
+        self.reservations.lock().expect("not poisoned").insert(
+            listener_id,
+            Reservation {
+                listener_id: listener_id.to_string(),
+                relay_peer_id,
+                address,
+                accepted: false,
+                renewed_at: None,
+            },
+        );
+    }
+
+    /// Mark the reservations on the relay as accepted
+    pub fn accepted(&self, relay_peer_id: PeerId, renewal: bool) {
+        {
+            let mut reservations = self.reservations.lock().expect("not poisoned");
+            for reservation in reservations
+                .values_mut()
+                .filter(|reservation| reservation.relay_peer_id == relay_peer_id)
+            {
+                reservation.accepted = true;
+                reservation.renewed_at = Some(now());
+            }
+        }
+
+        _ = self.events.send(RelayEvent::ReservationAccepted {
+            relay_peer_id,
+            renewal,
+        });
+    }
+
+    /// Remove the reservation once its listener is closed
+    pub fn closed(&self, listener_id: ListenerId, error: Option<String>) {
+        let reservation = self
+            .reservations
+            .lock()
+            .expect("not poisoned")
+            .remove(&listener_id);
+
+        if let Some(reservation) = reservation {
+            _ = self.events.send(RelayEvent::ReservationClosed {
+                listener_id: reservation.listener_id,
+                relay_peer_id: reservation.relay_peer_id,
+                error,
+            });
+        }
+    }
+
+    pub fn outbound_circuit(&self, relay_peer_id: PeerId) {
+        _ = self
+            .events
+            .send(RelayEvent::OutboundCircuitEstablished { relay_peer_id });
+    }
+
+    pub fn inbound_circuit(&self, src_peer_id: PeerId) {
+        _ = self
+            .events
+            .send(RelayEvent::InboundCircuitEstablished { src_peer_id });
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "relay")
+    }
+
+    fn find(&self, id: &str) -> Option<ListenerId> {
+        let reservations = self.reservations.lock().expect("not poisoned");
+        reservations
+            .keys()
+            .find(|listener_id| listener_id.to_string() == id)
+            .copied()
+    }
+
+    fn list(&self) -> Vec<Reservation> {
+        let reservations = self.reservations.lock().expect("not poisoned");
+        reservations.values().cloned().collect()
+    }
+}
+
+impl RelayServer {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn reservation_accepted(&self, src_peer_id: PeerId) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.reservations.insert(src_peer_id);
+    }
+
+    pub fn reservation_denied(&self) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.reservations_denied += 1;
+    }
+
+    pub fn reservation_closed(&self, src_peer_id: &PeerId) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.reservations.remove(src_peer_id);
+    }
+
+    pub fn circuit_accepted(&self, src_peer_id: PeerId, dst_peer_id: PeerId) {
+        let mut state = self.state.lock().expect("not poisoned");
+        *state
+            .circuits
+            .entry((src_peer_id, dst_peer_id))
+            .or_default() += 1;
+        state.circuits_accepted += 1;
+    }
+
+    pub fn circuit_denied(&self) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.circuits_denied += 1;
+    }
+
+    pub fn circuit_closed(&self, src_peer_id: PeerId, dst_peer_id: PeerId) {
+        let mut state = self.state.lock().expect("not poisoned");
+        let key = (src_peer_id, dst_peer_id);
+        if let Some(count) = state.circuits.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                state.circuits.remove(&key);
+            }
+        }
+    }
+
+    /// Drop the reservation of the peer once it is no longer connected, since the relay
+    /// would not report it as timed out
+    pub fn disconnected(&self, peer_id: &PeerId) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.reservations.remove(peer_id);
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "relay server")
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReserveParam {
+    /// Address of the relay, which must end with `/p2p/<relay peer id>`
+    #[schema(value_type = String)]
+    address: Multiaddr,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = relay::ReserveResponse)]
+pub struct ReserveResponse {
+    /// Id of the listener used to cancel the reservation
+    pub listener_id: String,
+    /// Circuit address listened on through the relay
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+}
+
+#[utoipa::path(
+    post,
+    path = "/reservations",
+    tag = "relay",
+    security(("bearer" = ["relay:write"])),
+    request_body = ReserveParam,
+    responses((status = 200, description = "Requesting a reservation on the relay, returning the id of the listener", body = ApiResponse<ReserveResponse>))
+)]
+pub async fn reserve(
+    State(connexa): State<Connexa>,
+    State(relay): State<Relay>,
+    Json(param): Json<ReserveParam>,
+) -> Result<Json<ApiResponse<ReserveResponse>>, ApiError> {
+    relay.ensure_enabled()?;
+
+    let mut address = param.address;
+    if let Some(Protocol::P2pCircuit) = address.iter().last() {
+        address.pop();
+    }
+
+    let Some(Protocol::P2p(relay_peer_id)) = address.iter().last() else {
+        return Err(ApiError::invalid_request(
+            "relay address must end with the peer id of the relay",
+        ));
+    };
+
+    let address = address.with(Protocol::P2pCircuit);
+    let listener_id = node::reserve(&connexa, address.clone(), relay_peer_id).await?;
+
+    Ok(ApiResponse::ok(ReserveResponse {
+        listener_id: listener_id.to_string(),
+        address,
+    }))
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ReservationsResponse {
+    pub reservations: Vec<Reservation>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/reservations",
+    tag = "relay",
+    security(("bearer" = ["relay:read"])),
+    responses((status = 200, description = "Reservations requested on relays", body = ApiResponse<ReservationsResponse>))
+)]
+pub async fn reservations(
+    State(relay): State<Relay>,
+) -> Result<Json<ApiResponse<ReservationsResponse>>, ApiError> {
+    relay.ensure_enabled()?;
+
+    Ok(ApiResponse::ok(ReservationsResponse {
+        reservations: relay.list(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/reservations/{listener_id}",
+    tag = "relay",
+    security(("bearer" = ["relay:write"])),
+    params(("listener_id" = String, Path, description = "Id of the listener returned when the reservation was requested")),
+    responses((status = 200, description = "Reservation cancelled", body = ApiResponse<Empty>))
+)]
+pub async fn cancel(
+    Path(listener_id): Path<String>,
+    State(connexa): State<Connexa>,
+    State(relay): State<Relay>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    relay.ensure_enabled()?;
+
+    let not_found = || {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!("reservation {listener_id} not found"),
+        )
+    };
+
+    let id = relay.find(&listener_id).ok_or_else(not_found)?;
+
+    if !node::remove_listener(&connexa, id).await? {
+        return Err(not_found());
+    }
+
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "relay",
+    security(("bearer" = ["relay:read"])),
+    responses((status = 200, description = "Stream of reservation and circuit events", content_type = "text/event-stream", body = RelayEvent))
+)]
+pub async fn listener(
+    State(relay): State<Relay>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    relay.ensure_enabled()?;
+
+    Ok(routes::event_stream(relay.events.subscribe()))
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ServerStats {
+    /// Number of peers currently holding a reservation on the node
+    pub active_reservations: usize,
+    /// Number of circuits currently relayed by the node
+    pub active_circuits: usize,
+    /// Peers currently holding a reservation on the node
+    #[schema(value_type = Vec<String>)]
+    pub reservation_peers: Vec<PeerId>,
+    pub reservations_denied: u64,
+    pub circuits_accepted: u64,
+    pub circuits_denied: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/server/stats",
+    tag = "relay",
+    security(("bearer" = ["relay:read"])),
+    responses((status = 200, description = "Reservations and circuits served by the node as a relay. Bytes relayed are not included, since connexa does not expose them", body = ApiResponse<ServerStats>))
+)]
+pub async fn server_stats(
+    State(server): State<RelayServer>,
+) -> Result<Json<ApiResponse<ServerStats>>, ApiError> {
+    server.ensure_enabled()?;
+
+    let state = server.state.lock().expect("not poisoned");
+
+    Ok(ApiResponse::ok(ServerStats {
+        active_reservations: state.reservations.len(),
+        active_circuits: state.circuits.values().sum(),
+        reservation_peers: state.reservations.iter().copied().collect(),
+        reservations_denied: state.reservations_denied,
+        circuits_accepted: state.circuits_accepted,
+        circuits_denied: state.circuits_denied,
+    }))
+}
@@ -1,76 +1,97 @@
-use axum::Json;
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Empty, Json, PeerAddresses};
 use axum::extract::State;
-use connexa::handle::Connexa;
 use connexa::prelude::PeerId;
-use serde::Deserialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+#[schema(as = rendezvous::Param)]
 pub struct Param {
+    /// Rendezvous point
+    #[schema(value_type = String)]
     peer_id: PeerId,
+    /// Namespace, which is required to register and unregister
     namespace: Option<String>,
+    /// Seconds the registration would be valid for
     ttl: Option<u64>, // TODO: impl cookie?
 }
 
-pub async fn register(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "rendezvous",
+    security(("bearer" = ["rendezvous:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Registered with the rendezvous point", body = ApiResponse<Empty>))
+)]
+pub async fn register(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
     let Some(namespace) = param.namespace else {
-        return Json(serde_json::json!({
-            "status": 500,
-            "message": "namespace is required"
-        }));
+        return Err(ApiError::invalid_request("namespace is required"));
     };
 
-    match connexa
+    connexa
         .rendezvous()
         .register(param.peer_id, namespace, param.ttl)
-        .await
-    {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+        .await?;
+
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn unregister(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
+#[utoipa::path(
+    delete,
+    path = "/unregister",
+    tag = "rendezvous",
+    security(("bearer" = ["rendezvous:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Unregistered from the rendezvous point", body = ApiResponse<Empty>))
+)]
+pub async fn unregister(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
     let Some(namespace) = param.namespace else {
-        return Json(serde_json::json!({
-            "status": 500,
-            "message": "namespace is required"
-        }));
+        return Err(ApiError::invalid_request("namespace is required"));
     };
 
-    match connexa
+    connexa
         .rendezvous()
         .unregister(param.peer_id, namespace)
-        .await
-    {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+        .await?;
+
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn discovery(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match connexa
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DiscoveryResponse {
+    pub peers: Vec<PeerAddresses>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/discover",
+    tag = "rendezvous",
+    security(("bearer" = ["rendezvous:read"])),
+    request_body = Param,
+    responses((status = 200, description = "Peers registered under the namespace", body = ApiResponse<DiscoveryResponse>))
+)]
+pub async fn discovery(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<DiscoveryResponse>>, ApiError> {
+    let (_, peers) = connexa
         .rendezvous()
         .discovery(param.peer_id, param.namespace, param.ttl, None)
-        .await
-    {
-        Ok((_, peers)) => Json(serde_json::json!({
-            "status": 200,
-            "peers": peers,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+        .await?;
+
+    Ok(ApiResponse::ok(DiscoveryResponse {
+        peers: peers
+            .into_iter()
+            .map(|(peer_id, addresses)| PeerAddresses(peer_id, addresses))
+            .collect(),
+    }))
 }
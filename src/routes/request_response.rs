@@ -0,0 +1,229 @@
+use crate::config::request_response::Config;
+use crate::error::{ApiError, ErrorCode};
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Body, Empty, Json, ProtocolParam, Query};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::response::Sse;
+use axum::response::sse::Event;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use connexa::prelude::request_response::InboundRequestId;
+use connexa::prelude::{PeerId, StreamProtocol};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Protocols configured for request-response along with the inbound requests awaiting a response
+#[derive(Clone)]
+pub struct RequestResponse {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    configs: Vec<Config>,
+    pending: Mutex<HashMap<(StreamProtocol, String), PendingRequest>>,
+}
+
+struct PendingRequest {
+    peer_id: PeerId,
+    request_id: InboundRequestId,
+    expires: Instant,
+}
+
+impl RequestResponse {
+    pub fn new(configs: Vec<Config>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                configs,
+                pending: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Largest request or response that would be accepted by any protocol
+    pub fn max_size(&self) -> usize {
+        self.inner
+            .configs
+            .iter()
+            .map(|config| config.max_request_size.max(config.max_response_size))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the config of the protocol
+    fn config(&self, protocol: String) -> Result<(StreamProtocol, &Config), ApiError> {
+        let config = self
+            .inner
+            .configs
+            .iter()
+            .find(|config| config.protocol == protocol)
+            .ok_or_else(|| {
+                ApiError::new(
+                    ErrorCode::NotFound,
+                    format!("protocol {protocol} not found"),
+                )
+            })?;
+
+        let protocol = StreamProtocol::try_from_owned(protocol)
+            .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+
+        Ok((protocol, config))
+    }
+
+    fn insert(
+        &self,
+        protocol: &StreamProtocol,
+        timeout: u64,
+        peer_id: PeerId,
+        request_id: InboundRequestId,
+    ) -> String {
+        let id = request_id.to_string();
+        let now = Instant::now();
+        let mut pending = self.inner.pending.lock().expect("not poisoned");
+        // Requests that were not answered in time would have already timed out
+        pending.retain(|_, request| request.expires > now);
+        pending.insert(
+            (protocol.clone(), id.clone()),
+            PendingRequest {
+                peer_id,
+                request_id,
+                expires: now + Duration::from_secs(timeout),
+            },
+        );
+        id
+    }
+
+    fn take(&self, protocol: &StreamProtocol, id: &str) -> Option<(PeerId, InboundRequestId)> {
+        let mut pending = self.inner.pending.lock().expect("not poisoned");
+        pending
+            .remove(&(protocol.clone(), id.to_string()))
+            .filter(|request| request.expires > Instant::now())
+            .map(|request| (request.peer_id, request.request_id))
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InboundRequest {
+    /// Id used to respond to the request
+    pub request_id: String,
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    /// Request encoded in base64
+    pub data: String,
+}
+
+fn too_large(kind: &str, max: usize) -> ApiError {
+    ApiError::new(
+        ErrorCode::PayloadTooLarge,
+        format!("{kind} exceeds the maximum size of {max} bytes"),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/send/{peer_id}",
+    tag = "request_response",
+    security(("bearer" = ["request_response:write"])),
+    params(("peer_id" = String, Path, description = "Peer to send the request to"), ProtocolParam),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = 200, description = "Response from the peer", content_type = "application/octet-stream", body = Vec<u8>))
+)]
+pub async fn send(
+    Path(peer_id): Path<String>,
+    Query(param): Query<ProtocolParam>,
+    State(connexa): State<Connexa>,
+    State(rr): State<RequestResponse>,
+    Body(body): Body,
+) -> Result<Bytes, ApiError> {
+    let (protocol, config) = rr.config(param.protocol)?;
+    let peer_id: PeerId = peer_id
+        .parse()
+        .map_err(|_| ApiError::invalid_request("invalid peer id"))?;
+
+    if body.len() > config.max_request_size {
+        return Err(too_large("request", config.max_request_size));
+    }
+
+    let response = connexa
+        .request_response()
+        .send_request(peer_id, (protocol, body))
+        .await?;
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/requests",
+    tag = "request_response",
+    security(("bearer" = ["request_response:read"])),
+    params(ProtocolParam),
+    responses((status = 200, description = "Stream of inbound requests", content_type = "text/event-stream", body = InboundRequest))
+)]
+pub async fn requests(
+    Query(param): Query<ProtocolParam>,
+    State(connexa): State<Connexa>,
+    State(rr): State<RequestResponse>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let (protocol, config) = rr.config(param.protocol)?;
+    let timeout = config.timeout;
+    let mut st = connexa
+        .request_response()
+        .listen_for_requests(protocol.clone())
+        .await?;
+
+    Ok(Sse::new(async_stream::try_stream! {
+        while let Some((peer_id, request_id, data)) = st.next().await {
+            let request = InboundRequest {
+                request_id: rr.insert(&protocol, timeout, peer_id, request_id),
+                peer_id,
+                data: STANDARD.encode(data),
+            };
+            if let Ok(event) = Event::default().json_data(request) {
+                yield event;
+            }
+        }
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/respond/{request_id}",
+    tag = "request_response",
+    security(("bearer" = ["request_response:write"])),
+    params(("request_id" = String, Path, description = "Id of the inbound request"), ProtocolParam),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses((status = 200, description = "Response sent to the peer", body = ApiResponse<Empty>))
+)]
+pub async fn respond(
+    Path(request_id): Path<String>,
+    Query(param): Query<ProtocolParam>,
+    State(connexa): State<Connexa>,
+    State(rr): State<RequestResponse>,
+    Body(body): Body,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    let (protocol, config) = rr.config(param.protocol)?;
+
+    if body.len() > config.max_response_size {
+        return Err(too_large("response", config.max_response_size));
+    }
+
+    let Some((peer_id, id)) = rr.take(&protocol, &request_id) else {
+        return Err(ApiError::new(
+            ErrorCode::NotFound,
+            format!("request {request_id} not found"),
+        ));
+    };
+
+    connexa
+        .request_response()
+        .send_response(peer_id, id, (protocol, body))
+        .await?;
+
+    Ok(ApiResponse::ok(Empty {}))
+}
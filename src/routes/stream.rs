@@ -0,0 +1,411 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::routes::{ApiResponse, Empty, Json, ProtocolParam, Query};
+use axum::body::Bytes;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use connexa::prelude::stream::{Control, IncomingStreams};
+use connexa::prelude::swarm::Stream;
+use connexa::prelude::{PeerId, StreamProtocol};
+use futures::{AsyncReadExt, AsyncWriteExt, SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+/// Size of the buffer used to read from a stream before forwarding it to the websocket
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Stream protocols registered at runtime, whose streams are bridged to websockets
+#[derive(Clone)]
+pub struct Streams {
+    control: Option<Control>,
+    protocols: Arc<Mutex<HashMap<StreamProtocol, Arc<Registered>>>>,
+}
+
+/// Inbound streams of a registered protocol, waiting to be picked up by a websocket handler
+struct Registered {
+    /// Taken once the protocol is unregistered, since the protocol stays registered with the
+    /// swarm until the inbound streams are dropped
+    incoming: tokio::sync::Mutex<Option<IncomingStreams>>,
+    /// Notify waiting handlers once the protocol is unregistered
+    closed: watch::Sender<bool>,
+}
+
+impl Registered {
+    /// Wait for the next inbound stream, returning none once the protocol is unregistered
+    async fn next(&self) -> Option<(PeerId, Stream)> {
+        let mut closed = self.closed.subscribe();
+        let next = async {
+            let mut incoming = self.incoming.lock().await;
+            incoming.as_mut()?.next().await
+        };
+
+        // Waiting handlers would otherwise hold the lock, preventing the streams from being taken
+        tokio::select! {
+            next = next => next,
+            _ = closed.wait_for(|closed| *closed) => None,
+        }
+    }
+}
+
+impl Streams {
+    /// Create the registry of stream protocols, where streams are disabled if there is no control
+    pub fn new(control: Option<Control>) -> Self {
+        Self {
+            control,
+            protocols: Arc::default(),
+        }
+    }
+
+    fn control(&self) -> Result<Control, ApiError> {
+        self.control
+            .clone()
+            .ok_or_else(|| ApiError::new(ErrorCode::ProtocolDisabled, "stream is not enabled"))
+    }
+
+    fn get(&self, protocol: &StreamProtocol) -> Result<Arc<Registered>, ApiError> {
+        let protocols = self.protocols.lock().expect("not poisoned");
+        protocols.get(protocol).cloned().ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::NotFound,
+                format!("protocol {protocol} is not registered"),
+            )
+        })
+    }
+}
+
+fn parse_protocol(protocol: String) -> Result<StreamProtocol, ApiError> {
+    StreamProtocol::try_from_owned(protocol).map_err(|e| ApiError::invalid_request(e.to_string()))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterParam {
+    /// Protocol to accept inbound streams on
+    protocol: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/protocols",
+    tag = "stream",
+    security(("bearer" = ["stream:write"])),
+    request_body = RegisterParam,
+    responses((status = 200, description = "Protocol registered", body = ApiResponse<Empty>))
+)]
+pub async fn register(
+    State(streams): State<Streams>,
+    Json(param): Json<RegisterParam>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    let mut control = streams.control()?;
+    let protocol = parse_protocol(param.protocol)?;
+
+    let incoming = control
+        .accept(protocol.clone())
+        .map_err(|e| ApiError::invalid_request(e.to_string()))?;
+
+    let (closed, _) = watch::channel(false);
+    streams.protocols.lock().expect("not poisoned").insert(
+        protocol,
+        Arc::new(Registered {
+            incoming: tokio::sync::Mutex::new(Some(incoming)),
+            closed,
+        }),
+    );
+
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ProtocolsResponse {
+    pub protocols: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/protocols",
+    tag = "stream",
+    security(("bearer" = ["stream:read"])),
+    responses((status = 200, description = "Protocols registered to accept inbound streams", body = ApiResponse<ProtocolsResponse>))
+)]
+pub async fn protocols(
+    State(streams): State<Streams>,
+) -> Result<Json<ApiResponse<ProtocolsResponse>>, ApiError> {
+    streams.control()?;
+
+    let protocols = streams
+        .protocols
+        .lock()
+        .expect("not poisoned")
+        .keys()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    Ok(ApiResponse::ok(ProtocolsResponse { protocols }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/protocols",
+    tag = "stream",
+    security(("bearer" = ["stream:write"])),
+    params(ProtocolParam),
+    responses((status = 200, description = "Protocol unregistered, closing any waiting handlers", body = ApiResponse<Empty>))
+)]
+pub async fn unregister(
+    Query(param): Query<ProtocolParam>,
+    State(streams): State<Streams>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    streams.control()?;
+    let protocol = parse_protocol(param.protocol)?;
+
+    let registered = streams.get(&protocol)?;
+    streams
+        .protocols
+        .lock()
+        .expect("not poisoned")
+        .remove(&protocol);
+    registered.closed.send_replace(true);
+    // Dropping the inbound streams releases the protocol so it could be registered again
+    registered.incoming.lock().await.take();
+
+    Ok(ApiResponse::ok(Empty {}))
+}
+
+#[utoipa::path(
+    get,
+    path = "/open/{peer_id}",
+    tag = "stream",
+    security(("bearer" = ["stream:write"])),
+    params(("peer_id" = String, Path, description = "Peer to open the stream with"), ProtocolParam),
+    responses((status = 101, description = "Websocket bridged to a new stream with the peer, where binary messages are written to the stream and data read from the stream is sent as binary messages"))
+)]
+pub async fn open(
+    Path(peer_id): Path<String>,
+    Query(param): Query<ProtocolParam>,
+    State(streams): State<Streams>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let mut control = streams.control()?;
+    let protocol = parse_protocol(param.protocol)?;
+    let peer_id: PeerId = peer_id
+        .parse()
+        .map_err(|_| ApiError::invalid_request("invalid peer id"))?;
+
+    // The stream is opened before upgrading so failures would be returned to the client
+    let stream = control
+        .open_stream(peer_id, protocol)
+        .await
+        .map_err(std::io::Error::other)?;
+
+    Ok(ws.on_upgrade(move |socket| bridge(socket, stream)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/accept",
+    tag = "stream",
+    security(("bearer" = ["stream:write"])),
+    params(ProtocolParam),
+    responses((status = 101, description = "Websocket handling the next inbound stream on the protocol. The peer id is sent as `{ \"peer_id\": <peer id> }` in a text message once a stream is accepted, after which the websocket is bridged to the stream"))
+)]
+pub async fn accept(
+    Query(param): Query<ProtocolParam>,
+    State(streams): State<Streams>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    streams.control()?;
+    let protocol = parse_protocol(param.protocol)?;
+    let registered = streams.get(&protocol)?;
+
+    Ok(ws.on_upgrade(move |socket| handle_inbound(socket, registered)))
+}
+
+/// Wait for the next inbound stream, giving up if the websocket is closed or the protocol is
+/// unregistered in the meantime
+async fn handle_inbound(mut socket: WebSocket, registered: Arc<Registered>) {
+    let next = registered.next();
+    tokio::pin!(next);
+
+    let (peer_id, stream) = loop {
+        tokio::select! {
+            next = &mut next => match next {
+                Some(next) => break next,
+                None => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        }
+    };
+
+    let message = serde_json::json!({ "peer_id": peer_id }).to_string();
+    if socket.send(Message::Text(message.into())).await.is_err() {
+        return;
+    }
+
+    bridge(socket, stream).await;
+}
+
+/// Forward binary and text messages from the websocket to the stream, and data read from the
+/// stream to the websocket as binary messages, until either side is closed
+async fn bridge(socket: WebSocket, stream: Stream) {
+    let (mut reader, mut writer) = stream.split();
+    let (mut sink, mut source) = socket.split();
+
+    let inbound = async {
+        while let Some(Ok(message)) = source.next().await {
+            let data = match message {
+                Message::Binary(data) => data,
+                Message::Text(text) => Bytes::from(text.as_str().to_owned()),
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            if writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    let outbound = async {
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            match reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let data = Bytes::copy_from_slice(&buffer[..n]);
+                    if sink.send(Message::Binary(data)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    };
+
+    // Once either side ends both are closed, since the other side could otherwise be left
+    // waiting on a peer or client that has nothing more to send
+    tokio::select! {
+        _ = inbound => {}
+        _ = outbound => {}
+    }
+    _ = writer.close().await;
+    _ = sink.close().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProtocolFlags;
+    use crate::node;
+    use crate::node::tests::{no_protocols, spawn_node};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn unregister_releases_protocol() {
+        let protocols = ProtocolFlags {
+            stream: true,
+            ..no_protocols()
+        };
+        let (connexa, _) = spawn_node(protocols).await;
+        let streams = Streams::new(node::stream_control(&connexa).await.unwrap());
+        let register = || {
+            register(
+                State(streams.clone()),
+                Json(RegisterParam {
+                    protocol: "/echo".into(),
+                }),
+            )
+        };
+
+        register().await.unwrap();
+
+        // Handlers keep the registration alive until they exit, which happens after unregistering
+        let registered = streams.get(&StreamProtocol::new("/echo")).unwrap();
+        let waiting = tokio::spawn({
+            let registered = registered.clone();
+            async move { registered.next().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let param = ProtocolParam {
+            protocol: "/echo".into(),
+        };
+        unregister(Query(param), State(streams.clone()))
+            .await
+            .unwrap();
+        register().await.unwrap();
+
+        assert!(waiting.await.unwrap().is_none());
+        drop(registered);
+    }
+
+    #[tokio::test]
+    async fn bridge_closes_stream_once_websocket_closes() {
+        use axum::Router;
+        use axum::routing::get;
+        use futures::channel::oneshot;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let protocols = || ProtocolFlags {
+            stream: true,
+            ..no_protocols()
+        };
+        let (a, _) = spawn_node(protocols()).await;
+        let (b, b_addr) = spawn_node(protocols()).await;
+        let b_peer_id = b.keypair().public().to_peer_id();
+        let echo = StreamProtocol::new("/echo");
+
+        let mut incoming = node::stream_control(&b)
+            .await
+            .unwrap()
+            .unwrap()
+            .accept(echo.clone())
+            .unwrap();
+        a.swarm().dial(b_addr).await.unwrap();
+        let stream = node::stream_control(&a)
+            .await
+            .unwrap()
+            .unwrap()
+            .open_stream(b_peer_id, echo)
+            .await
+            .unwrap();
+        let (_, mut remote) = incoming.next().await.unwrap();
+
+        let (done_tx, done_rx) = oneshot::channel();
+        let bridged = Arc::new(Mutex::new(Some((stream, done_tx))));
+        let app = Router::new().route(
+            "/",
+            get(move |ws: WebSocketUpgrade| async move {
+                let (stream, done) = bridged.lock().unwrap().take().unwrap();
+                ws.on_upgrade(move |socket| async move {
+                    bridge(socket, stream).await;
+                    _ = done.send(());
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/"))
+            .await
+            .unwrap();
+        client
+            .send(WsMessage::Binary(b"hello".to_vec().into()))
+            .await
+            .unwrap();
+        let mut buffer = [0; 5];
+        remote.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"hello");
+
+        // The peer keeps its side open, which must not keep the bridge alive
+        client.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), done_rx)
+            .await
+            .expect("bridge should end")
+            .unwrap();
+        assert_eq!(remote.read(&mut buffer).await.unwrap(), 0);
+    }
+}
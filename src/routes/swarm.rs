@@ -1,278 +1,892 @@
-use axum::Json;
+use crate::error::{ApiError, ErrorCode};
+use crate::node::{self, Connexa};
+use crate::routes::identify::Identify;
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json, Query};
 use axum::extract::State;
-use axum::http::StatusCode;
 use axum::response::Sse;
 use axum::response::sse::Event;
-use connexa::handle::Connexa;
-use connexa::prelude::swarm::ConnectionId;
 use connexa::prelude::swarm::dial_opts::DialOpts;
-use connexa::prelude::{ConnectionEvent, ConnectionTarget, Multiaddr, PeerId};
+use connexa::prelude::swarm::{ConnectionId, SwarmEvent};
+use connexa::prelude::transport::ConnectedPoint;
+use connexa::prelude::transport::transport::ListenerId;
+use connexa::prelude::{ConnectionTarget, Multiaddr, PeerId, Protocol};
 use futures::Stream;
-use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Deserialize)]
+/// Listeners of the node along with the addresses they resolved to, which are kept up to date
+/// from swarm events
+#[derive(Clone)]
+pub struct Listeners {
+    inner: Arc<Mutex<HashMap<ListenerId, ListenerInfo>>>,
+    events: broadcast::Sender<ListenEvent>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ListenerInfo {
+    pub listener_id: String,
+    /// Address requested when the listener was added
+    #[schema(value_type = Option<String>)]
+    pub address: Option<Multiaddr>,
+    /// Addresses the listener is currently listening on
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Multiaddr>,
+}
+
+impl ListenerInfo {
+    fn new(listener_id: ListenerId) -> Self {
+        Self {
+            listener_id: listener_id.to_string(),
+            address: None,
+            addresses: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ListenEvent {
+    NewListenAddr {
+        listener_id: String,
+        #[schema(value_type = String)]
+        address: Multiaddr,
+    },
+    ExpiredListenAddr {
+        listener_id: String,
+        #[schema(value_type = String)]
+        address: Multiaddr,
+    },
+    ListenerClosed {
+        listener_id: String,
+        /// Reason the listener was closed, if it was closed due to an error
+        error: Option<String>,
+    },
+}
+
+impl Default for Listeners {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            inner: Arc::default(),
+            events,
+        }
+    }
+}
+
+impl Listeners {
+    /// Record the address requested for the listener
+    pub fn insert(&self, listener_id: ListenerId, address: Multiaddr) {
+        let mut inner = self.inner.lock().expect("not poisoned");
+        inner
+            .entry(listener_id)
+            .or_insert_with(|| ListenerInfo::new(listener_id))
+            .address = Some(address);
+    }
+
+    pub fn new_listen_addr(&self, listener_id: ListenerId, address: &Multiaddr) {
+        let mut inner = self.inner.lock().expect("not poisoned");
+        let info = inner
+            .entry(listener_id)
+            .or_insert_with(|| ListenerInfo::new(listener_id));
+        if !info.addresses.contains(address) {
+            info.addresses.push(address.clone());
+        }
+        _ = self.events.send(ListenEvent::NewListenAddr {
+            listener_id: listener_id.to_string(),
+            address: address.clone(),
+        });
+    }
+
+    pub fn expired_listen_addr(&self, listener_id: ListenerId, address: &Multiaddr) {
+        let mut inner = self.inner.lock().expect("not poisoned");
+        if let Some(info) = inner.get_mut(&listener_id) {
+            info.addresses.retain(|addr| addr != address);
+        }
+        _ = self.events.send(ListenEvent::ExpiredListenAddr {
+            listener_id: listener_id.to_string(),
+            address: address.clone(),
+        });
+    }
+
+    pub fn closed(&self, listener_id: ListenerId, error: Option<String>) {
+        self.inner
+            .lock()
+            .expect("not poisoned")
+            .remove(&listener_id);
+        _ = self.events.send(ListenEvent::ListenerClosed {
+            listener_id: listener_id.to_string(),
+            error,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ListenEvent> {
+        self.events.subscribe()
+    }
+
+    fn find(&self, id: &str) -> Option<ListenerId> {
+        let inner = self.inner.lock().expect("not poisoned");
+        inner
+            .keys()
+            .find(|listener_id| listener_id.to_string() == id)
+            .copied()
+    }
+
+    fn list(&self) -> Vec<ListenerInfo> {
+        let inner = self.inner.lock().expect("not poisoned");
+        inner.values().cloned().collect()
+    }
+}
+
+/// Message of responses that do not return anything else
+const SUCCESS: &str = "success";
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Success {
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DialParam {
+    /// Peer to dial. If not provided, exactly one address is required
+    #[schema(value_type = Option<String>)]
     pub peer_id: Option<PeerId>,
+    #[schema(value_type = Vec<String>)]
     pub addresses: Vec<Multiaddr>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct DisconnectParam {
+    /// Peer to disconnect from
+    #[schema(value_type = Option<String>)]
     pub peer_id: Option<PeerId>,
-    pub connection_id: Option<usize>,
+    /// Connection to close, which takes priority over `peer_id`. Accepts the id either as a
+    /// number or as the string returned by `/swarm/dial` and `/swarm/connections`
+    #[schema(value_type = Option<String>)]
+    pub connection_id: Option<ConnectionIdParam>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConnectionIdParam {
+    Number(usize),
+    String(String),
 }
 
 impl DisconnectParam {
-    fn to_connection_target(self) -> Option<ConnectionTarget> {
+    fn into_connection_target(self) -> Result<ConnectionTarget, ApiError> {
         let DisconnectParam {
             peer_id,
             connection_id,
         } = self;
+        let connection_id = match connection_id {
+            Some(ConnectionIdParam::Number(id)) => Some(id),
+            Some(ConnectionIdParam::String(id)) => Some(
+                id.parse()
+                    .map_err(|_| ApiError::invalid_request("invalid connection id"))?,
+            ),
+            None => None,
+        };
         match (peer_id, connection_id) {
-            (None, None) => None,
-            (Some(peer_id), None) => Some(ConnectionTarget::PeerId(peer_id)),
-            (None, Some(id)) | (Some(_), Some(id)) => Some(ConnectionTarget::ConnectionId(
+            (None, None) => Err(ApiError::invalid_request("invalid disconnect param")),
+            (Some(peer_id), None) => Ok(ConnectionTarget::PeerId(peer_id)),
+            (None, Some(id)) | (Some(_), Some(id)) => Ok(ConnectionTarget::ConnectionId(
                 ConnectionId::new_unchecked(id),
             )),
         }
     }
 }
 
-pub async fn dial(State(connexa): State<Connexa>, Json(param): Json<DialParam>) -> Json<Value> {
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DialResponse {
+    pub message: String,
+    /// Id of the connection being dialed
+    pub connection_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/dial",
+    tag = "swarm",
+    security(("bearer" = ["swarm:write"])),
+    request_body = DialParam,
+    responses((status = 200, description = "Dialing the peer, returning the id of the connection", body = ApiResponse<DialResponse>))
+)]
+pub async fn dial(
+    State(connexa): State<Connexa>,
+    Json(param): Json<DialParam>,
+) -> Result<Json<ApiResponse<DialResponse>>, ApiError> {
     let opt = match param.peer_id {
         Some(peer_id) => DialOpts::peer_id(peer_id)
             .addresses(param.addresses)
             .build(),
         None if param.addresses.len() == 1 => DialOpts::unknown_peer_id()
-            .address(param.addresses.get(0).cloned().expect("should exist"))
+            .address(param.addresses.first().cloned().expect("should exist"))
             .build(),
-        None => {
-            let status = StatusCode::BAD_REQUEST;
-            return Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": "invalid dial param"
-            }));
-        }
+        None => return Err(ApiError::invalid_request("invalid dial param")),
     };
 
-    let id = match connexa.swarm().dial(opt).await {
-        Ok(id) => id,
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            return Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }));
-        }
-    };
+    let id = connexa.swarm().dial(opt).await?;
 
-    Json(serde_json::json!({
-        "status": 200,
-        "message": "success",
-        "connection_id": id.to_string()
+    Ok(ApiResponse::ok(DialResponse {
+        message: SUCCESS.into(),
+        connection_id: id.to_string(),
     }))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/disconnect",
+    tag = "swarm",
+    security(("bearer" = ["swarm:write"])),
+    request_body = DisconnectParam,
+    responses((status = 200, description = "Disconnected from the peer or connection", body = ApiResponse<Success>))
+)]
 pub async fn disconnect(
     State(connexa): State<Connexa>,
     Json(param): Json<DisconnectParam>,
-) -> Json<Value> {
-    let connection_target = match param.to_connection_target() {
-        Some(connection_target) => connection_target,
-        None => {
-            let status = StatusCode::BAD_REQUEST;
-            return Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": "invalid disconnect param"
-            }));
-        }
-    };
+) -> Result<Json<ApiResponse<Success>>, ApiError> {
+    let connection_target = param.into_connection_target()?;
 
-    if let Err(e) = connexa.swarm().disconnect(connection_target).await {
-        let status = StatusCode::INTERNAL_SERVER_ERROR;
-        return Json(serde_json::json!({
-            "status": status.as_u16(),
-            "message": e.to_string()
-        }));
-    }
+    connexa.swarm().disconnect(connection_target).await?;
 
-    Json(serde_json::json!({
-        "status": 200,
-        "message": "success",
+    Ok(ApiResponse::ok(Success {
+        message: SUCCESS.into(),
     }))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct IsConnectedParam {
+    #[param(value_type = String)]
     pub peer_id: PeerId,
 }
 
+#[derive(Serialize, Debug, ToSchema)]
+pub struct IsConnectedResponse {
+    pub connected: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/is_connected",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    params(IsConnectedParam),
+    responses((status = 200, description = "Whether the peer is connected", body = ApiResponse<IsConnectedResponse>))
+)]
 pub async fn is_connected(
     State(connexa): State<Connexa>,
-    Json(param): Json<IsConnectedParam>,
-) -> Json<Value> {
-    match connexa.swarm().is_connected(param.peer_id).await {
-        Ok(connected) => Json(serde_json::json!({
-            "status": 200,
-            "connected": connected,
-        })),
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
-        }
-    }
+    Query(param): Query<IsConnectedParam>,
+) -> Result<Json<ApiResponse<IsConnectedResponse>>, ApiError> {
+    let connected = connexa.swarm().is_connected(param.peer_id).await?;
+    Ok(ApiResponse::ok(IsConnectedResponse { connected }))
 }
 
-pub async fn connected_peers(State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.swarm().connected_peers().await {
-        Ok(peers) => {
-            Json(serde_json::json!({
-                "status": 200,
-                "peers": peers,
-            }))
-        }
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
-        }
-    }
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = swarm::PeersResponse)]
+pub struct PeersResponse {
+    #[schema(value_type = Vec<String>)]
+    pub peers: Vec<PeerId>,
 }
 
-pub async fn listening_addresses(State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.swarm().listening_addresses().await {
-        Ok(addresses) => {
-            Json(serde_json::json!({
-                "status": 200,
-                "addresses": addresses,
-            }))
-        }
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
+#[utoipa::path(
+    get,
+    path = "/connected",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Peers currently connected", body = ApiResponse<PeersResponse>))
+)]
+pub async fn connected_peers(
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<PeersResponse>>, ApiError> {
+    let peers = connexa.swarm().connected_peers().await?;
+    Ok(ApiResponse::ok(PeersResponse { peers }))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConnectionsParam {
+    /// Only list connections to this peer
+    #[param(value_type = Option<String>)]
+    pub peer_id: Option<PeerId>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ConnectionsResponse {
+    pub connections: Vec<ConnectionInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/connections",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    params(ConnectionsParam),
+    responses((status = 200, description = "Open connections of the node", body = ApiResponse<ConnectionsResponse>))
+)]
+pub async fn connections(
+    Query(param): Query<ConnectionsParam>,
+    State(connections): State<Connections>,
+    State(identify): State<Identify>,
+) -> Json<ApiResponse<ConnectionsResponse>> {
+    let mut list = connections.list(param.peer_id);
+    for info in list.iter_mut() {
+        if let Some(peer) = identify.get(&info.peer_id) {
+            info.peer_protocols = peer.protocols;
         }
     }
+
+    ApiResponse::ok(ConnectionsResponse { connections: list })
 }
 
-// #[derive(Debug, Deserialize)]
-// pub struct ListenParam {
-//     pub address: Multiaddr,
-// }
-//
-// pub async fn listen_on(
-//     State(connexa): State<Connexa>,
-//     Json(param): Json<ListenParam>,
-// ) -> Json<Value> {
-//     match connexa.swarm().listen_on(param.address.clone()).await {
-//         Ok(listener_id) => Json(serde_json::json!({
-//             "status": 200,
-//             "listener_id": listener_id.to_string(),
-//             "address": param.address.to_string()
-//         })),
-//         Err(e) => {
-//             let status = StatusCode::INTERNAL_SERVER_ERROR;
-//             Json(serde_json::json!({
-//                 "status": status.as_u16(),
-//                 "message": e.to_string()
-//             }))
-//         }
-//     }
-// }
-
-pub async fn external_addresses(State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.swarm().external_addresses().await {
-        Ok(addresses) => {
-            Json(serde_json::json!({
-                "status": 200,
-                "external_addresses": addresses,
-            }))
-        }
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
-        }
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AddressesResponse {
+    #[schema(value_type = Vec<String>)]
+    pub addresses: Vec<Multiaddr>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/listening_addresses",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Addresses the node is listening on", body = ApiResponse<AddressesResponse>))
+)]
+pub async fn listening_addresses(
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<AddressesResponse>>, ApiError> {
+    let addresses = connexa.swarm().listening_addresses().await?;
+    Ok(ApiResponse::ok(AddressesResponse { addresses }))
+}
+
+/// Alias of [`listening_addresses`]
+#[utoipa::path(
+    get,
+    path = "/addresses",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Addresses the node is listening on", body = ApiResponse<AddressesResponse>))
+)]
+pub async fn addresses(
+    state: State<Connexa>,
+) -> Result<Json<ApiResponse<AddressesResponse>>, ApiError> {
+    listening_addresses(state).await
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ListenParam {
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ListenResponse {
+    /// Id of the listener used to remove it
+    pub listener_id: String,
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+}
+
+#[utoipa::path(
+    post,
+    path = "/listen_on",
+    tag = "swarm",
+    security(("bearer" = ["swarm:write"])),
+    request_body = ListenParam,
+    responses((status = 200, description = "Listening on the address, returning the id of the listener", body = ApiResponse<ListenResponse>))
+)]
+pub async fn listen_on(
+    State(connexa): State<Connexa>,
+    Json(param): Json<ListenParam>,
+) -> Result<Json<ApiResponse<ListenResponse>>, ApiError> {
+    let listener_id = node::listen_on(&connexa, param.address.clone()).await?;
+
+    Ok(ApiResponse::ok(ListenResponse {
+        listener_id: listener_id.to_string(),
+        address: param.address,
+    }))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RemoveListenerParam {
+    pub listener_id: String,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/remove_listener",
+    tag = "swarm",
+    security(("bearer" = ["swarm:write"])),
+    request_body = RemoveListenerParam,
+    responses((status = 200, description = "Listener removed", body = ApiResponse<Success>))
+)]
+pub async fn remove_listener(
+    State(connexa): State<Connexa>,
+    State(listeners): State<Listeners>,
+    Json(param): Json<RemoveListenerParam>,
+) -> Result<Json<ApiResponse<Success>>, ApiError> {
+    let not_found = || {
+        ApiError::new(
+            ErrorCode::NotFound,
+            format!("listener {} not found", param.listener_id),
+        )
+    };
+
+    let listener_id = listeners.find(&param.listener_id).ok_or_else(not_found)?;
+
+    if !node::remove_listener(&connexa, listener_id).await? {
+        return Err(not_found());
     }
+
+    Ok(ApiResponse::ok(Success {
+        message: SUCCESS.into(),
+    }))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ListenersResponse {
+    pub listeners: Vec<ListenerInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/listeners",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Listeners of the node along with the addresses they resolved to", body = ApiResponse<ListenersResponse>))
+)]
+pub async fn listeners(State(listeners): State<Listeners>) -> Json<ApiResponse<ListenersResponse>> {
+    ApiResponse::ok(ListenersResponse {
+        listeners: listeners.list(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/listeners/events",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Stream of listen address events", content_type = "text/event-stream", body = ListenEvent))
+)]
+pub async fn listen_events(
+    State(listeners): State<Listeners>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    routes::event_stream(listeners.events.subscribe())
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ExternalAddressesResponse {
+    #[schema(value_type = Vec<String>)]
+    pub external_addresses: Vec<Multiaddr>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/external_addresses",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Confirmed external addresses of the node", body = ApiResponse<ExternalAddressesResponse>))
+)]
+pub async fn external_addresses(
+    State(connexa): State<Connexa>,
+) -> Result<Json<ApiResponse<ExternalAddressesResponse>>, ApiError> {
+    let addresses = connexa.swarm().external_addresses().await?;
+    Ok(ApiResponse::ok(ExternalAddressesResponse {
+        external_addresses: addresses,
+    }))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AddPeerAddressParam {
+    #[schema(value_type = String)]
     pub peer_id: PeerId,
+    #[schema(value_type = String)]
     pub address: Multiaddr,
 }
 
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AddPeerAddressResponse {
+    pub message: String,
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+}
+
+#[utoipa::path(
+    post,
+    path = "/add_peer_address",
+    tag = "swarm",
+    security(("bearer" = ["swarm:write"])),
+    request_body = AddPeerAddressParam,
+    responses((status = 200, description = "Address added for the peer", body = ApiResponse<AddPeerAddressResponse>))
+)]
 pub async fn add_peer_address(
     State(connexa): State<Connexa>,
     Json(param): Json<AddPeerAddressParam>,
-) -> Json<Value> {
-    match connexa
+) -> Result<Json<ApiResponse<AddPeerAddressResponse>>, ApiError> {
+    connexa
         .swarm()
         .add_peer_address(param.peer_id, param.address.clone())
-        .await
-    {
-        Ok(()) => Json(serde_json::json!({
-            "status": 200,
-            "message": "success",
-            "peer_id": param.peer_id,
-            "address": param.address
-        })),
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
+        .await?;
+
+    Ok(ApiResponse::ok(AddPeerAddressResponse {
+        message: SUCCESS.into(),
+        peer_id: param.peer_id,
+        address: param.address,
+    }))
+}
+
+/// Open connections of the node along with connection events, which are forwarded from swarm
+/// events to any listeners
+#[derive(Clone)]
+pub struct Connections {
+    inner: Arc<Mutex<HashMap<ConnectionId, ConnectionInfo>>>,
+    events: broadcast::Sender<ConnectionListenerEvent>,
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            inner: Arc::default(),
+            events,
+        }
+    }
+}
+
+impl Connections {
+    /// Keep track of open connections and forward the swarm event to any listeners if it is a
+    /// connection event
+    pub fn notify<T>(&self, event: &SwarmEvent<T>) {
+        match event {
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                ..
+            } => {
+                let info = ConnectionInfo::new(*peer_id, *connection_id, endpoint);
+                let mut inner = self.inner.lock().expect("not poisoned");
+                inner.insert(*connection_id, info);
+            }
+            SwarmEvent::ConnectionClosed { connection_id, .. } => {
+                let mut inner = self.inner.lock().expect("not poisoned");
+                inner.remove(connection_id);
+            }
+            _ => {}
+        }
+
+        // Avoid converting the event if no one is listening
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+
+        if let Some(event) = ConnectionListenerEvent::new(event) {
+            _ = self.events.send(event);
+        }
+    }
+
+    pub fn list(&self, peer_id: Option<PeerId>) -> Vec<ConnectionInfo> {
+        let inner = self.inner.lock().expect("not poisoned");
+        let mut list: Vec<_> = inner
+            .values()
+            .filter(|info| peer_id.is_none_or(|peer_id| info.peer_id == peer_id))
+            .cloned()
+            .collect();
+        list.sort_by_key(|info| info.established_at);
+        list
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ConnectionInfo {
+    #[schema(value_type = String)]
+    pub peer_id: PeerId,
+    pub connection_id: String,
+    /// Remote address of the connection
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+    /// Local address of the connection. This is null for connections dialed by the node, since
+    /// libp2p does not report the local address of outbound connections
+    #[schema(value_type = Option<String>)]
+    pub local_address: Option<Multiaddr>,
+    pub direction: Role,
+    pub transport: Transport,
+    /// Seconds since the unix epoch when the connection was established
+    pub established_at: u64,
+    /// Protocols the peer advertised through identify, which are not necessarily the protocols
+    /// negotiated on this connection
+    pub peer_protocols: Vec<String>,
+}
+
+impl ConnectionInfo {
+    fn new(peer_id: PeerId, connection_id: ConnectionId, endpoint: &ConnectedPoint) -> Self {
+        let address = endpoint.get_remote_address().clone();
+        Self {
+            peer_id,
+            connection_id: connection_id.to_string(),
+            // Connections accepted through a relay would only have the relayed local address
+            transport: match endpoint.is_relayed() {
+                true => Transport::Relayed,
+                false => Transport::from(&address),
+            },
+            address,
+            local_address: local_address(endpoint),
+            direction: endpoint.into(),
+            established_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            peer_protocols: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Tcp,
+    Quic,
+    Websocket,
+    Webrtc,
+    /// Connected through a relay
+    Relayed,
+    Unknown,
+}
+
+impl From<&Multiaddr> for Transport {
+    fn from(address: &Multiaddr) -> Self {
+        // A relayed address would also contain the transport used to reach the relay
+        if address.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+            return Transport::Relayed;
+        }
+
+        let mut transport = Transport::Unknown;
+        for protocol in address.iter() {
+            transport = match protocol {
+                Protocol::WebRTCDirect | Protocol::WebRTC => return Transport::Webrtc,
+                Protocol::Ws(_) | Protocol::Wss(_) => return Transport::Websocket,
+                Protocol::QuicV1 | Protocol::Quic => Transport::Quic,
+                Protocol::Tcp(_) => Transport::Tcp,
+                _ => continue,
+            };
+        }
+        transport
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// The connection was dialed by the node
+    Dialer,
+    /// The connection was accepted by a listener of the node
+    Listener,
+}
+
+impl From<&ConnectedPoint> for Role {
+    fn from(endpoint: &ConnectedPoint) -> Self {
+        match endpoint {
+            ConnectedPoint::Dialer { .. } => Role::Dialer,
+            ConnectedPoint::Listener { .. } => Role::Listener,
         }
     }
 }
 
-#[derive(Serialize, Debug)]
+/// Local address of the connection, which is only known for connections accepted by a listener
+fn local_address(endpoint: &ConnectedPoint) -> Option<Multiaddr> {
+    match endpoint {
+        ConnectedPoint::Dialer { .. } => None,
+        ConnectedPoint::Listener { local_addr, .. } => Some(local_addr.clone()),
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ConnectionListenerEvent {
-    ConnectionEstablished { peer_id: PeerId, address: Multiaddr },
-    ConnectionClosed { peer_id: PeerId, address: Multiaddr },
+    ConnectionEstablished {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        connection_id: String,
+        role: Role,
+        /// Remote address of the connection
+        #[schema(value_type = String)]
+        address: Multiaddr,
+        /// Local address of the connection, which is null for connections dialed by the node
+        #[schema(value_type = Option<String>)]
+        local_address: Option<Multiaddr>,
+        /// Number of connections established with the peer, including this one
+        num_established: u32,
+        /// Milliseconds it took to establish the connection
+        established_in: u64,
+    },
+    ConnectionClosed {
+        #[schema(value_type = String)]
+        peer_id: PeerId,
+        connection_id: String,
+        role: Role,
+        /// Remote address of the connection
+        #[schema(value_type = String)]
+        address: Multiaddr,
+        #[schema(value_type = Option<String>)]
+        local_address: Option<Multiaddr>,
+        /// Number of connections remaining with the peer
+        num_established: u32,
+        /// Error that caused the connection to close, if it was not closed gracefully
+        cause: Option<String>,
+    },
+    /// A connection is being dialed
+    Dialing {
+        #[schema(value_type = Option<String>)]
+        peer_id: Option<PeerId>,
+        connection_id: String,
+    },
+    /// A connection is being accepted by a listener
+    IncomingConnection {
+        connection_id: String,
+        /// Remote address of the connection
+        #[schema(value_type = String)]
+        address: Multiaddr,
+        #[schema(value_type = String)]
+        local_address: Multiaddr,
+    },
+    IncomingConnectionError {
+        connection_id: String,
+        /// Remote address of the connection
+        #[schema(value_type = String)]
+        address: Multiaddr,
+        #[schema(value_type = String)]
+        local_address: Multiaddr,
+        error: String,
+    },
+    OutgoingConnectionError {
+        #[schema(value_type = Option<String>)]
+        peer_id: Option<PeerId>,
+        connection_id: String,
+        error: String,
+    },
+    NewListenAddr {
+        listener_id: String,
+        #[schema(value_type = String)]
+        address: Multiaddr,
+    },
+    ExpiredListenAddr {
+        listener_id: String,
+        #[schema(value_type = String)]
+        address: Multiaddr,
+    },
 }
 
-pub async fn connection_listener(
-    State(connexa): State<Connexa>,
-) -> Sse<impl Stream<Item=Result<Event, Infallible>>> {
-    let mut st = connexa
-        .swarm()
-        .listener()
-        .await
-        .expect("valid listener")
-        .map(|event| match event {
-            ConnectionEvent::ConnectionEstablished {
-                peer_id, endpoint, ..
-            } => ConnectionListenerEvent::ConnectionEstablished {
+impl ConnectionListenerEvent {
+    fn new<T>(event: &SwarmEvent<T>) -> Option<Self> {
+        let event = match event {
+            SwarmEvent::ConnectionEstablished {
                 peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                established_in,
+                ..
+            } => Self::ConnectionEstablished {
+                peer_id: *peer_id,
+                connection_id: connection_id.to_string(),
+                role: endpoint.into(),
                 address: endpoint.get_remote_address().clone(),
+                local_address: local_address(endpoint),
+                num_established: num_established.get(),
+                established_in: established_in.as_millis() as u64,
             },
-            ConnectionEvent::ConnectionClosed {
-                peer_id, endpoint, ..
-            } => ConnectionListenerEvent::ConnectionClosed {
+            SwarmEvent::ConnectionClosed {
                 peer_id,
+                connection_id,
+                endpoint,
+                num_established,
+                cause,
+                ..
+            } => Self::ConnectionClosed {
+                peer_id: *peer_id,
+                connection_id: connection_id.to_string(),
+                role: endpoint.into(),
                 address: endpoint.get_remote_address().clone(),
+                local_address: local_address(endpoint),
+                num_established: *num_established,
+                cause: cause.as_ref().map(ToString::to_string),
+            },
+            SwarmEvent::Dialing {
+                peer_id,
+                connection_id,
+                ..
+            } => Self::Dialing {
+                peer_id: *peer_id,
+                connection_id: connection_id.to_string(),
             },
-        })
-        .map(|e| Event::default().json_data(e));
+            SwarmEvent::IncomingConnection {
+                connection_id,
+                local_addr,
+                send_back_addr,
+                ..
+            } => Self::IncomingConnection {
+                connection_id: connection_id.to_string(),
+                address: send_back_addr.clone(),
+                local_address: local_addr.clone(),
+            },
+            SwarmEvent::IncomingConnectionError {
+                connection_id,
+                local_addr,
+                send_back_addr,
+                error,
+                ..
+            } => Self::IncomingConnectionError {
+                connection_id: connection_id.to_string(),
+                address: send_back_addr.clone(),
+                local_address: local_addr.clone(),
+                error: error.to_string(),
+            },
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                peer_id,
+                error,
+                ..
+            } => Self::OutgoingConnectionError {
+                peer_id: *peer_id,
+                connection_id: connection_id.to_string(),
+                error: error.to_string(),
+            },
+            SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } => Self::NewListenAddr {
+                listener_id: listener_id.to_string(),
+                address: address.clone(),
+            },
+            SwarmEvent::ExpiredListenAddr {
+                listener_id,
+                address,
+            } => Self::ExpiredListenAddr {
+                listener_id: listener_id.to_string(),
+                address: address.clone(),
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+}
 
-    Sse::new(async_stream::try_stream! {
-        while let Some(Ok(event)) = st.next().await {
-            yield event;
-        }
-    })
+#[utoipa::path(
+    get,
+    path = "/listener",
+    tag = "swarm",
+    security(("bearer" = ["swarm:read"])),
+    responses((status = 200, description = "Stream of connection events, including dial failures and listen address changes", content_type = "text/event-stream", body = ConnectionListenerEvent))
+)]
+pub async fn connection_listener(
+    State(connections): State<Connections>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    routes::event_stream(connections.events.subscribe())
 }
@@ -0,0 +1,372 @@
+use crate::error::ApiError;
+use crate::routes::{self, ApiResponse, EVENT_CAPACITY, Json};
+use axum::extract::State;
+use axum::response::Sse;
+use axum::response::sse::Event;
+use connexa::prelude::{Multiaddr, Protocol};
+use futures::Stream;
+use igd_next::SearchOptions;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Gateway discovered through upnp and the addresses mapped on it, along with listeners of
+/// mapping events
+#[derive(Clone)]
+pub struct Upnp {
+    enabled: bool,
+    state: Arc<Mutex<UpnpState>>,
+    events: broadcast::Sender<UpnpEvent>,
+}
+
+#[derive(Default)]
+struct UpnpState {
+    gateway: Gateway,
+    info: Option<GatewayInfo>,
+    mappings: HashMap<Multiaddr, Mapping>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Gateway {
+    /// No gateway was discovered yet
+    #[default]
+    Searching,
+    /// A gateway was discovered, which ports would be mapped on
+    Found,
+    /// No gateway was discovered on the network
+    NotFound,
+    /// A gateway was discovered, but its external address is not routable
+    NonRoutable,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct GatewayInfo {
+    /// Address the gateway responded to the discovery from
+    #[schema(value_type = String)]
+    pub address: SocketAddr,
+    /// Location of the device description of the gateway
+    pub root_url: String,
+    /// External ip address reported by the gateway
+    #[schema(value_type = String)]
+    pub external_ip: IpAddr,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Mapping {
+    /// External address mapped on the gateway
+    #[schema(value_type = String)]
+    pub address: Multiaddr,
+    /// External port mapped on the gateway
+    pub port: Option<u16>,
+    /// Either `tcp` or `udp`
+    pub protocol: Option<String>,
+    /// Seconds since the unix epoch when the mapping was created
+    pub created_at: u64,
+}
+
+impl Mapping {
+    fn new(address: Multiaddr) -> Self {
+        let (protocol, port) = address
+            .iter()
+            .find_map(|protocol| match protocol {
+                Protocol::Tcp(port) => Some(("tcp", port)),
+                Protocol::Udp(port) => Some(("udp", port)),
+                _ => None,
+            })
+            .unzip();
+
+        Self {
+            address,
+            port,
+            protocol: protocol.map(ToString::to_string),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpnpEvent {
+    /// A gateway was discovered on the network
+    GatewayFound { gateway: GatewayInfo },
+    /// A port was mapped on the gateway, making the node reachable at the address
+    MappingCreated { mapping: Mapping },
+    /// A mapping expired and could not be renewed
+    MappingExpired {
+        #[schema(value_type = String)]
+        address: Multiaddr,
+    },
+    /// Ports could not be mapped, since no usable gateway was discovered
+    MappingFailed { gateway: Gateway },
+}
+
+impl Upnp {
+    pub fn new(enabled: bool) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            enabled,
+            state: Arc::default(),
+            events,
+        }
+    }
+
+    pub fn new_external_addr(&self, address: Multiaddr) {
+        let mapping = Mapping::new(address.clone());
+        {
+            let mut state = self.state.lock().expect("not poisoned");
+            // A port can only be mapped on a gateway, even if the search of the node missed it
+            state.gateway = Gateway::Found;
+            state.mappings.insert(address, mapping.clone());
+        }
+
+        _ = self.events.send(UpnpEvent::MappingCreated { mapping });
+    }
+
+    pub fn expired_external_addr(&self, address: &Multiaddr) {
+        self.state
+            .lock()
+            .expect("not poisoned")
+            .mappings
+            .remove(address);
+
+        _ = self.events.send(UpnpEvent::MappingExpired {
+            address: address.clone(),
+        });
+    }
+
+    /// Record that no usable gateway was discovered
+    pub fn failed(&self, gateway: Gateway) {
+        self.state.lock().expect("not poisoned").gateway = gateway;
+        _ = self.events.send(UpnpEvent::MappingFailed { gateway });
+    }
+
+    /// Search for a gateway on the network, recording the gateway found along with its external
+    /// ip address. Note that ports are mapped separately by the upnp behaviour of the node, which
+    /// only reports the mappings and not the gateway itself
+    pub async fn discover(&self, options: SearchOptions) {
+        let result = async {
+            let gateway = igd_next::aio::tokio::search_gateway(options)
+                .await
+                .map_err(|e| e.to_string())?;
+            let external_ip = gateway.get_external_ip().await.map_err(|e| e.to_string())?;
+            Ok::<_, String>(GatewayInfo {
+                address: gateway.addr,
+                root_url: gateway.root_url,
+                external_ip,
+            })
+        };
+
+        match result.await {
+            Ok(info) => {
+                {
+                    let mut state = self.state.lock().expect("not poisoned");
+                    // The behaviour could have already reported the gateway as unusable
+                    if state.gateway == Gateway::Searching {
+                        state.gateway = Gateway::Found;
+                    }
+                    state.info = Some(info.clone());
+                }
+                _ = self.events.send(UpnpEvent::GatewayFound { gateway: info });
+            }
+            Err(_) => {
+                let mut state = self.state.lock().expect("not poisoned");
+                if state.gateway == Gateway::Searching {
+                    state.gateway = Gateway::NotFound;
+                }
+            }
+        }
+    }
+
+    fn ensure_enabled(&self) -> Result<(), ApiError> {
+        routes::ensure_enabled(self.enabled, "upnp")
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = upnp::StatusResponse)]
+pub struct StatusResponse {
+    pub gateway: Gateway,
+    /// Gateway discovered on the network, if any
+    pub gateway_info: Option<GatewayInfo>,
+    /// External addresses mapped on the gateway
+    pub mappings: Vec<Mapping>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "upnp",
+    security(("bearer" = ["upnp:read"])),
+    responses((status = 200, description = "Result of the gateway discovery along with the mapped external addresses", body = ApiResponse<StatusResponse>))
+)]
+pub async fn status(
+    State(upnp): State<Upnp>,
+) -> Result<Json<ApiResponse<StatusResponse>>, ApiError> {
+    upnp.ensure_enabled()?;
+
+    let state = upnp.state.lock().expect("not poisoned");
+    let mappings = state.mappings.values().cloned().collect::<Vec<_>>();
+
+    Ok(ApiResponse::ok(StatusResponse {
+        gateway: state.gateway,
+        gateway_info: state.info.clone(),
+        mappings,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "upnp",
+    security(("bearer" = ["upnp:read"])),
+    responses((status = 200, description = "Stream of gateways found and mappings created, expired or failed", content_type = "text/event-stream", body = UpnpEvent))
+)]
+pub async fn listener(
+    State(upnp): State<Upnp>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    upnp.ensure_enabled()?;
+
+    Ok(routes::event_stream(upnp.events.subscribe()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::routing::{get, post};
+    use std::time::Duration;
+    use tokio::net::{TcpListener, UdpSocket};
+
+    const ROOT_DESC: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+        <SCPDURL>/scpd.xml</SCPDURL>
+        <controlURL>/ctl</controlURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    const SCPD: &str = r#"<?xml version="1.0"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <actionList>
+    <action>
+      <name>GetExternalIPAddress</name>
+      <argumentList>
+        <argument>
+          <name>NewExternalIPAddress</name>
+          <direction>out</direction>
+        </argument>
+      </argumentList>
+    </action>
+  </actionList>
+</scpd>"#;
+
+    const EXTERNAL_IP: &str = r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/">
+  <s:Body>
+    <u:GetExternalIPAddressResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1">
+      <NewExternalIPAddress>203.0.113.7</NewExternalIPAddress>
+    </u:GetExternalIPAddressResponse>
+  </s:Body>
+</s:Envelope>"#;
+
+    /// Start a stand-in gateway answering the search over udp and serving its description and
+    /// control endpoint over http, returning the address searches should be sent to
+    async fn stand_in_gateway() -> SocketAddr {
+        let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http_addr = http.local_addr().unwrap();
+        let app = Router::new()
+            .route("/rootDesc.xml", get(|| async { ROOT_DESC }))
+            .route("/scpd.xml", get(|| async { SCPD }))
+            .route("/ctl", post(|| async { EXTERNAL_IP }));
+        tokio::spawn(async move { axum::serve(http, app).await });
+
+        let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let udp_addr = udp.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            while let Ok((_, from)) = udp.recv_from(&mut buf).await {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nLOCATION: http://{http_addr}/rootDesc.xml\r\n\r\n"
+                );
+                _ = udp.send_to(response.as_bytes(), from).await;
+            }
+        });
+
+        udp_addr
+    }
+
+    fn search_options(broadcast_address: SocketAddr) -> SearchOptions {
+        SearchOptions {
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            broadcast_address,
+            timeout: Some(Duration::from_secs(2)),
+            single_search_timeout: Some(Duration::from_millis(500)),
+        }
+    }
+
+    #[tokio::test]
+    async fn gateway_found_before_mapping() {
+        let gateway_addr = stand_in_gateway().await;
+        let upnp = Upnp::new(true);
+        let mut events = upnp.events.subscribe();
+
+        upnp.discover(search_options(gateway_addr)).await;
+
+        {
+            let state = upnp.state.lock().unwrap();
+            assert_eq!(state.gateway, Gateway::Found);
+            assert!(state.mappings.is_empty());
+            let info = state.info.as_ref().expect("gateway info");
+            assert_eq!(info.root_url, "/rootDesc.xml");
+            assert_eq!(info.external_ip, IpAddr::from([203, 0, 113, 7]));
+        }
+
+        assert!(matches!(
+            events.try_recv(),
+            Ok(UpnpEvent::GatewayFound { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn gateway_not_found() {
+        // Nothing answers on this socket, so the search times out
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upnp = Upnp::new(true);
+
+        upnp.discover(search_options(silent.local_addr().unwrap()))
+            .await;
+
+        let state = upnp.state.lock().unwrap();
+        assert_eq!(state.gateway, Gateway::NotFound);
+        assert!(state.info.is_none());
+    }
+
+    #[tokio::test]
+    async fn mapping_marks_gateway_found() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upnp = Upnp::new(true);
+
+        upnp.discover(search_options(silent.local_addr().unwrap()))
+            .await;
+        upnp.new_external_addr("/ip4/203.0.113.7/tcp/4001".parse().unwrap());
+
+        let state = upnp.state.lock().unwrap();
+        assert_eq!(state.gateway, Gateway::Found);
+        assert_eq!(state.mappings.len(), 1);
+    }
+}
@@ -1,54 +1,65 @@
-use axum::Json;
+use crate::error::ApiError;
+use crate::node::Connexa;
+use crate::routes::{ApiResponse, Empty, Json};
 use axum::extract::State;
-use axum::http::StatusCode;
-use connexa::handle::Connexa;
 use connexa::prelude::PeerId;
-use serde::Deserialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+#[schema(as = whitelist::Param)]
 pub struct Param {
+    #[schema(value_type = String)]
     peer_id: PeerId,
 }
 
-pub async fn add(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match connexa.whitelist().add(param.peer_id).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+#[utoipa::path(
+    post,
+    path = "/add",
+    tag = "whitelist",
+    security(("bearer" = ["whitelist:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Peer added to the whitelist", body = ApiResponse<Empty>))
+)]
+pub async fn add(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.whitelist().add(param.peer_id).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn remove(State(connexa): State<Connexa>, Json(param): Json<Param>) -> Json<Value> {
-    match connexa.whitelist().remove(param.peer_id).await {
-        Ok(_) => Json(serde_json::json!({
-            "status": 200,
-        })),
-        Err(e) => Json(serde_json::json!({
-            "status": 500,
-            "message": e.to_string()
-        })),
-    }
+#[utoipa::path(
+    delete,
+    path = "/remove",
+    tag = "whitelist",
+    security(("bearer" = ["whitelist:write"])),
+    request_body = Param,
+    responses((status = 200, description = "Peer removed from the whitelist", body = ApiResponse<Empty>))
+)]
+pub async fn remove(
+    State(connexa): State<Connexa>,
+    Json(param): Json<Param>,
+) -> Result<Json<ApiResponse<Empty>>, ApiError> {
+    connexa.whitelist().remove(param.peer_id).await?;
+    Ok(ApiResponse::ok(Empty {}))
 }
 
-pub async fn list(State(connexa): State<Connexa>) -> Json<Value> {
-    match connexa.whitelist().list().await {
-        Ok(peers) => {
-            Json(serde_json::json!({
-                "status": 200,
-                "peers": peers,
-            }))
-        }
-        Err(e) => {
-            let status = StatusCode::INTERNAL_SERVER_ERROR;
-            Json(serde_json::json!({
-                "status": status.as_u16(),
-                "message": e.to_string()
-            }))
-        }
-    }
+#[derive(Serialize, Debug, ToSchema)]
+#[schema(as = whitelist::Peers)]
+pub struct Peers {
+    #[schema(value_type = Vec<String>)]
+    peers: Vec<PeerId>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/list",
+    tag = "whitelist",
+    security(("bearer" = ["whitelist:read"])),
+    responses((status = 200, description = "Peers in the whitelist", body = ApiResponse<Peers>))
+)]
+pub async fn list(State(connexa): State<Connexa>) -> Result<Json<ApiResponse<Peers>>, ApiError> {
+    let peers = connexa.whitelist().list().await?;
+    Ok(ApiResponse::ok(Peers { peers }))
 }
@@ -0,0 +1,75 @@
+mod tls;
+
+use crate::config::Http;
+use axum::Router;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Binds every http address provided and serves the api on each of them until one of them stops
+pub async fn serve(http: &[Http], app: Router) -> std::io::Result<()> {
+    if http.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "at least one http address is required",
+        ));
+    }
+
+    let mut servers = Vec::with_capacity(http.len());
+
+    // We bind every address before serving so a failure would be reported before the api is
+    // available on any of the addresses
+    for http in http {
+        let server = bind(http, app.clone())
+            .await
+            .map_err(|e| std::io::Error::new(e.kind(), format!("failed to bind {http}: {e}")))?;
+        println!("Serving API at {http}");
+        servers.push(server);
+    }
+
+    let (result, _, _) = futures::future::select_all(servers).await;
+    result
+}
+
+async fn bind(
+    http: &Http,
+    app: Router,
+) -> std::io::Result<BoxFuture<'static, std::io::Result<()>>> {
+    match http {
+        Http::Tcp { host, port, tls } => {
+            let listener = TcpListener::bind(SocketAddr::new(*host, *port)).await?;
+            match tls {
+                Some(config) => {
+                    let listener = tls::TlsListener::new(listener, config)?;
+                    Ok(axum::serve(listener, app).into_future().boxed())
+                }
+                None => Ok(axum::serve(listener, app).into_future().boxed()),
+            }
+        }
+        #[cfg(unix)]
+        Http::Unix { path } => {
+            use std::os::unix::fs::FileTypeExt;
+            // Remove a stale socket that may have been left behind from a previous run, but only
+            // when nothing answers on it so we would not take over a socket still in use
+            if let Ok(metadata) = std::fs::symlink_metadata(path)
+                && metadata.file_type().is_socket()
+            {
+                if tokio::net::UnixStream::connect(path).await.is_ok() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AddrInUse,
+                        "socket is in use by another process",
+                    ));
+                }
+                std::fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            Ok(axum::serve(listener, app).into_future().boxed())
+        }
+        #[cfg(not(unix))]
+        Http::Unix { .. } => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "unix domain sockets are not supported on this platform",
+        )),
+    }
+}
@@ -0,0 +1,242 @@
+use crate::config::tls::Config;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+
+/// Listener that terminates tls for every connection accepted.
+///
+/// Handshakes are performed in their own task so a slow or misbehaving client would not
+/// prevent other connections from being accepted. The number of handshakes in progress is
+/// limited so clients that never complete the handshake could not exhaust the server
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    task: JoinHandle<()>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: &Config) -> std::io::Result<Self> {
+        let acceptor = TlsAcceptor::from(Arc::new(server_config(config)?));
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(64);
+        let handshake_timeout = Duration::from_secs(config.handshake_timeout);
+        let handshakes = Arc::new(Semaphore::new(config.max_handshakes));
+
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok(permit) = handshakes.clone().acquire_owned().await else {
+                    break;
+                };
+
+                let (stream, addr) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        // Errors are usually from reaching the limit of open files so we would
+                        // wait before accepting another connection
+                        println!("failed to accept connection on {local_addr}: {e}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let result =
+                        tokio::time::timeout(handshake_timeout, acceptor.accept(stream)).await;
+                    drop(permit);
+                    match result {
+                        Ok(Ok(stream)) => _ = tx.send((stream, addr)).await,
+                        Ok(Err(e)) => println!("tls handshake with {addr} failed: {e}"),
+                        Err(_) => println!("tls handshake with {addr} timed out"),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            incoming,
+            task,
+        })
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            // The accept task is only stopped when the listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+fn server_config(config: &Config) -> std::io::Result<ServerConfig> {
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+
+    let certificates = CertificateDer::pem_file_iter(&config.certificates)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| pem_error(&config.certificates, e))?;
+
+    let private_key = PrivateKeyDer::from_pem_file(&config.private_key)
+        .map_err(|e| pem_error(&config.private_key, e))?;
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(std::io::Error::other)?;
+
+    let builder = match &config.client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for certificate in
+                CertificateDer::pem_file_iter(path).map_err(|e| pem_error(path, e))?
+            {
+                roots
+                    .add(certificate.map_err(|e| pem_error(path, e))?)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certificates, private_key)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(server_config)
+}
+
+fn pem_error(path: &std::path::Path, e: rustls_pki_types::pem::Error) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("failed to read {}: {e}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::serve::Listener;
+    use rustls_pki_types::ServerName;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::ClientConfig;
+
+    /// Write a self-signed certificate for localhost, returning the config pointing at it along
+    /// with the certificate
+    fn self_signed(name: &str) -> (Config, CertificateDer<'static>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("connexa-http-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let certificates = dir.join("cert.pem");
+        let private_key = dir.join("key.pem");
+        std::fs::write(&certificates, certified.cert.pem()).unwrap();
+        std::fs::write(&private_key, certified.signing_key.serialize_pem()).unwrap();
+
+        let config = Config {
+            certificates,
+            private_key,
+            client_ca: None::<PathBuf>,
+            handshake_timeout: 1,
+            max_handshakes: 1,
+        };
+        (config, certified.cert.der().clone())
+    }
+
+    fn connector(certificate: CertificateDer<'static>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate).unwrap();
+        let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    #[tokio::test]
+    async fn handshake() {
+        let (config, certificate) = self_signed("handshake");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut listener = TlsListener::new(listener, &config).unwrap();
+        let addr = Listener::local_addr(&listener).unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let mut stream = connector(certificate)
+                .connect(server_name, stream)
+                .await
+                .unwrap();
+            stream.write_all(b"ping").await.unwrap();
+            stream.flush().await.unwrap();
+            stream
+        });
+
+        let (mut stream, _) = listener.accept().await;
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        _ = client.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stalled_handshake_times_out() {
+        let (config, certificate) = self_signed("stalled");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut listener = TlsListener::new(listener, &config).unwrap();
+        let addr = Listener::local_addr(&listener).unwrap();
+
+        // Holds the only handshake permit without ever starting the handshake, so the next
+        // client is only accepted once the handshake times out
+        let _stalled = TcpStream::connect(addr).await.unwrap();
+        let started = std::time::Instant::now();
+
+        tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let stream = connector(certificate)
+                .connect(server_name, stream)
+                .await
+                .unwrap();
+            std::future::pending::<()>().await;
+            drop(stream);
+        });
+
+        let accepted = tokio::time::timeout(Duration::from_secs(5), listener.accept()).await;
+        assert!(accepted.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(900));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::config::Config;
+use crate::node::Connexa;
+use crate::routes::autonat::Autonat;
+use crate::routes::dcutr::Dcutr;
+use crate::routes::gossipsub::Validation;
+use crate::routes::identify::Identify;
+use crate::routes::kademlia::Kademlia;
+use crate::routes::mdns::Mdns;
+use crate::routes::ping::Ping;
+use crate::routes::relay::{Relay, RelayServer};
+use crate::routes::request_response::RequestResponse;
+use crate::routes::stream::Streams;
+use crate::routes::swarm::{Connections, Listeners};
+use crate::routes::upnp::Upnp;
+use axum::extract::FromRef;
+
+/// State shared by every route. Routes would extract the part they need through [`FromRef`]
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub connexa: Connexa,
+    pub request_response: RequestResponse,
+    pub streams: Streams,
+    pub validation: Validation,
+    pub identify: Identify,
+    pub kademlia: Kademlia,
+    pub listeners: Listeners,
+    pub connections: Connections,
+    pub ping: Ping,
+    pub autonat: Autonat,
+    pub relay: Relay,
+    pub relay_server: RelayServer,
+    pub dcutr: Dcutr,
+    pub upnp: Upnp,
+    pub mdns: Mdns,
+}
+
+impl AppState {
+    pub fn new(
+        connexa: Connexa,
+        request_response: RequestResponse,
+        streams: Streams,
+        state: NodeState,
+    ) -> Self {
+        let NodeState {
+            validation,
+            identify,
+            kademlia,
+            listeners,
+            connections,
+            ping,
+            autonat,
+            relay,
+            relay_server,
+            dcutr,
+            upnp,
+            mdns,
+        } = state;
+
+        Self {
+            connexa,
+            request_response,
+            streams,
+            validation,
+            identify,
+            kademlia,
+            listeners,
+            connections,
+            ping,
+            autonat,
+            relay,
+            relay_server,
+            dcutr,
+            upnp,
+            mdns,
+        }
+    }
+}
+
+/// State kept up to date by the node from swarm events and read by the routes
+#[derive(Clone)]
+pub struct NodeState {
+    pub validation: Validation,
+    pub identify: Identify,
+    pub kademlia: Kademlia,
+    pub listeners: Listeners,
+    pub connections: Connections,
+    pub ping: Ping,
+    pub autonat: Autonat,
+    pub relay: Relay,
+    pub relay_server: RelayServer,
+    pub dcutr: Dcutr,
+    pub upnp: Upnp,
+    pub mdns: Mdns,
+}
+
+impl NodeState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            validation: Validation::new(config.gossipsub.validate_messages),
+            identify: Identify::new(config.protocols.identify),
+            kademlia: Kademlia::new(config.kademlia.mode),
+            listeners: Listeners::default(),
+            connections: Connections::default(),
+            ping: Ping::new(config.protocols.ping),
+            autonat: Autonat::new(config.protocols.autonat),
+            relay: Relay::new(config.protocols.relay),
+            relay_server: RelayServer::new(config.protocols.relay),
+            dcutr: Dcutr::new(config.protocols.dcutr),
+            upnp: Upnp::new(config.protocols.upnp),
+            mdns: Mdns::new(config.protocols.mdns),
+        }
+    }
+}